@@ -1,5 +1,5 @@
 
-@('glitch', './glitch', 'glitch.exe', '.\glitch', '.\glitch.exe', './glitch.exe') | %{
+@('glitch', './glitch') | %{
     Register-ArgumentCompleter -Native -CommandName $_ -ScriptBlock {
         param($wordToComplete, $commandAst, $cursorPosition)
 
@@ -19,8 +19,13 @@
                         break
                     }
 
-                    'completion' {
-                        $command += '_completion'
+                    'recipe' {
+                        $command += '_recipe'
+                        break
+                    }
+
+                    'dump' {
+                        $command += '_dump'
                         break
                     }
 
@@ -29,6 +34,26 @@
                         break
                     }
 
+                    'presets' {
+                        $command += '_presets'
+                        break
+                    }
+
+                    'list' {
+                        $command += '_list'
+                        break
+                    }
+
+                    'effects' {
+                        $command += '_effects'
+                        break
+                    }
+
+                    'completion' {
+                        $command += '_completion'
+                        break
+                    }
+
                     default { 
                         break
                     }
@@ -40,11 +65,39 @@
         switch ($command) {
 
             '_glitch' {
-                $completions = @('render', 'completion', 'help', '-h', '-V', '--help', '--version')
+                $completions = @('render', 'recipe', 'presets', 'effects', 'completion', 'help', '-h', '-V', '--help', '--version')
             }
 
             '_glitch_render' {
-                $completions = @('-h', '-V', '-n', '--help', '--version', '--number', '--color-shift', '--scan-height', '--scan-gap', '--desync-amp', '--desync-freq', '--wind-onset', '--wind-continue', '--blocks')
+                $completions = @('-h', '-V', '-n', '-o', '--loop', '--help', '--version', '--number', '--format', '--output', '--frame-delay', '--repeat', '--gif-speed', '--color-shift', '--shift-mode', '--shift-offset', '--shift-radius', '--shift-max-move', '--scan-height', '--scan-gap', '--desync-amp', '--desync-freq', '--wind-onset', '--wind-continue', '--wind-direction', '--wind-fade', '--wind-fade-mode', '--blocks', '--grain-alpha', '--grain-frequency', '--grain-octaves', '--grain-persistence', '--grain-lacunarity', '--grain-color', '--grain-blend', '--grain-motion', '--grain-speed', '--preset', '--recipe', '--pipeline', '--pipeline-file', '--keyframe', '--seed')
+            }
+
+            '_glitch_recipe' {
+                $completions = @('dump', 'help', '-h', '-V', '--help', '--version')
+            }
+
+            '_glitch_recipe_dump' {
+                $completions = @('-h', '-V', '--help', '--version', '--color-shift', '--shift-mode', '--shift-offset', '--shift-radius', '--shift-max-move', '--scan-height', '--scan-gap', '--desync-amp', '--desync-freq', '--wind-onset', '--wind-continue', '--wind-direction', '--wind-fade', '--wind-fade-mode', '--blocks', '--grain-alpha', '--grain-frequency', '--grain-octaves', '--grain-persistence', '--grain-lacunarity', '--grain-color', '--grain-blend', '--grain-motion', '--grain-speed', '--preset', '--recipe', '--pipeline', '--pipeline-file', '--keyframe')
+            }
+
+            '_glitch_recipe_help' {
+                $completions = @('-h', '-V', '--help', '--version')
+            }
+
+            '_glitch_presets' {
+                $completions = @('list', 'help', '-h', '-V', '--help', '--version')
+            }
+
+            '_glitch_presets_list' {
+                $completions = @('-h', '-V', '--help', '--version')
+            }
+
+            '_glitch_presets_help' {
+                $completions = @('-h', '-V', '--help', '--version')
+            }
+
+            '_glitch_effects' {
+                $completions = @('-h', '-V', '--help', '--version')
             }
 
             '_glitch_completion' {
@@ -16,9 +16,24 @@ _glitch() {
             completion)
                 cmd+="__completion"
                 ;;
+            dump)
+                cmd+="__dump"
+                ;;
+            effects)
+                cmd+="__effects"
+                ;;
             help)
                 cmd+="__help"
                 ;;
+            list)
+                cmd+="__list"
+                ;;
+            presets)
+                cmd+="__presets"
+                ;;
+            recipe)
+                cmd+="__recipe"
+                ;;
             render)
                 cmd+="__render"
                 ;;
@@ -29,7 +44,7 @@ _glitch() {
 
     case "${cmd}" in
         glitch)
-            opts=" -h -V  --help --version   render completion help"
+            opts=" -h -V  --help --version   render recipe presets effects completion help"
             if [[ ${cur} == -* || ${COMP_CWORD} -eq 1 ]] ; then
                 COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
                 return 0
@@ -59,6 +74,21 @@ _glitch() {
             COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
             return 0
             ;;
+        glitch__effects)
+            opts=" -h -V  --help --version  "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
         glitch__help)
             opts=" -h -V  --help --version  "
             if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
@@ -74,8 +104,214 @@ _glitch() {
             COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
             return 0
             ;;
+        glitch__presets)
+            opts=" -h -V  --help --version   list help"
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        glitch__presets__help)
+            opts=" -h -V  --help --version  "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 3 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        glitch__presets__list)
+            opts=" -h -V  --help --version  "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 3 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        glitch__recipe)
+            opts=" -h -V  --help --version   dump help"
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        glitch__recipe__dump)
+            opts=" -h -V  --help --version --color-shift --shift-mode --shift-offset --shift-radius --shift-max-move --scan-height --scan-gap --desync-amp --desync-freq --wind-onset --wind-continue --wind-direction --wind-fade --wind-fade-mode --blocks --grain-alpha --grain-frequency --grain-octaves --grain-persistence --grain-lacunarity --grain-color --grain-blend --grain-motion --grain-speed --preset --recipe --pipeline --pipeline-file --keyframe  <FILE> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 3 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --color-shift)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --shift-mode)
+                    COMPREPLY=($(compgen -W "red-cyan rgb" -- ${cur}))
+                    return 0
+                    ;;
+                --shift-offset)
+                    COMPREPLY=("<N|R,G,B>")
+                    return 0
+                    ;;
+                --shift-radius)
+                    COMPREPLY=("<N|R,G,B>")
+                    return 0
+                    ;;
+                --shift-max-move)
+                    COMPREPLY=("<N|R,G,B>")
+                    return 0
+                    ;;
+                --scan-height)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --scan-gap)
+                    COMPREPLY=("<M>")
+                    return 0
+                    ;;
+                --desync-amp)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --desync-freq)
+                    COMPREPLY=("<M>")
+                    return 0
+                    ;;
+                --wind-onset)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --wind-continue)
+                    COMPREPLY=("<M>")
+                    return 0
+                    ;;
+                --wind-direction)
+                    COMPREPLY=("<DIR>")
+                    return 0
+                    ;;
+                --wind-fade)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --wind-fade-mode)
+                    COMPREPLY=($(compgen -W "alpha blend" -- ${cur}))
+                    return 0
+                    ;;
+                --blocks)
+                    COMPREPLY=("<M>")
+                    return 0
+                    ;;
+                --grain-alpha)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --grain-frequency)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --grain-octaves)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --grain-persistence)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --grain-lacunarity)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --grain-color)
+                    COMPREPLY=($(compgen -W "mono rgb" -- ${cur}))
+                    return 0
+                    ;;
+                --grain-blend)
+                    COMPREPLY=($(compgen -W "add overlay" -- ${cur}))
+                    return 0
+                    ;;
+                --grain-motion)
+                    COMPREPLY=($(compgen -W "reseed coherent" -- ${cur}))
+                    return 0
+                    ;;
+                --grain-speed)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --preset)
+                    COMPREPLY=($(compgen -W "subtle vhs cyberkitten cyberpunk datamosh-lite" -- ${cur}))
+                    return 0
+                    ;;
+                --recipe)
+                    COMPREPLY=("<FILE>")
+                    return 0
+                    ;;
+                --pipeline)
+                    COMPREPLY=("<SPEC>")
+                    return 0
+                    ;;
+                --pipeline-file)
+                    COMPREPLY=("<FILE>")
+                    return 0
+                    ;;
+                --keyframe)
+                    COMPREPLY=("<SPEC>...")
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        glitch__recipe__help)
+            opts=" -h -V  --help --version  "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 3 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
         glitch__render)
-            opts=" -h -V -n  --help --version --number --color-shift --scan-height --scan-gap --desync-amp --desync-freq --wind-onset --wind-continue --blocks  <FILE> "
+            opts=" -h -V -n -o  --loop --help --version --number --format --output --frame-delay --repeat --gif-speed --color-shift --shift-mode --shift-offset --shift-radius --shift-max-move --scan-height --scan-gap --desync-amp --desync-freq --wind-onset --wind-continue --wind-direction --wind-fade --wind-fade-mode --blocks --grain-alpha --grain-frequency --grain-octaves --grain-persistence --grain-lacunarity --grain-color --grain-blend --grain-motion --grain-speed --preset --recipe --pipeline --pipeline-file --keyframe --seed  <FILE> "
             if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
                 COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
                 return 0
@@ -90,10 +326,50 @@ _glitch() {
                     COMPREPLY=("<N>")
                     return 0
                     ;;
+                --format)
+                    COMPREPLY=($(compgen -W "png gif apng webp" -- ${cur}))
+                    return 0
+                    ;;
+                --output)
+                    COMPREPLY=("<TEMPLATE>")
+                    return 0
+                    ;;
+                    -o)
+                    COMPREPLY=("<TEMPLATE>")
+                    return 0
+                    ;;
+                --frame-delay)
+                    COMPREPLY=("<MS>")
+                    return 0
+                    ;;
+                --repeat)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --gif-speed)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
                 --color-shift)
                     COMPREPLY=("<N>")
                     return 0
                     ;;
+                --shift-mode)
+                    COMPREPLY=($(compgen -W "red-cyan rgb" -- ${cur}))
+                    return 0
+                    ;;
+                --shift-offset)
+                    COMPREPLY=("<N|R,G,B>")
+                    return 0
+                    ;;
+                --shift-radius)
+                    COMPREPLY=("<N|R,G,B>")
+                    return 0
+                    ;;
+                --shift-max-move)
+                    COMPREPLY=("<N|R,G,B>")
+                    return 0
+                    ;;
                 --scan-height)
                     COMPREPLY=("<N>")
                     return 0
@@ -118,10 +394,82 @@ _glitch() {
                     COMPREPLY=("<M>")
                     return 0
                     ;;
+                --wind-direction)
+                    COMPREPLY=("<DIR>")
+                    return 0
+                    ;;
+                --wind-fade)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --wind-fade-mode)
+                    COMPREPLY=($(compgen -W "alpha blend" -- ${cur}))
+                    return 0
+                    ;;
                 --blocks)
                     COMPREPLY=("<M>")
                     return 0
                     ;;
+                --grain-alpha)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --grain-frequency)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --grain-octaves)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --grain-persistence)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --grain-lacunarity)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --grain-color)
+                    COMPREPLY=($(compgen -W "mono rgb" -- ${cur}))
+                    return 0
+                    ;;
+                --grain-blend)
+                    COMPREPLY=($(compgen -W "add overlay" -- ${cur}))
+                    return 0
+                    ;;
+                --grain-motion)
+                    COMPREPLY=($(compgen -W "reseed coherent" -- ${cur}))
+                    return 0
+                    ;;
+                --grain-speed)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
+                --preset)
+                    COMPREPLY=($(compgen -W "subtle vhs cyberkitten cyberpunk datamosh-lite" -- ${cur}))
+                    return 0
+                    ;;
+                --recipe)
+                    COMPREPLY=("<FILE>")
+                    return 0
+                    ;;
+                --pipeline)
+                    COMPREPLY=("<SPEC>")
+                    return 0
+                    ;;
+                --pipeline-file)
+                    COMPREPLY=("<FILE>")
+                    return 0
+                    ;;
+                --keyframe)
+                    COMPREPLY=("<SPEC>...")
+                    return 0
+                    ;;
+                --seed)
+                    COMPREPLY=("<N>")
+                    return 0
+                    ;;
                 *)
                     COMPREPLY=()
                     ;;
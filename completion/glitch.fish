@@ -14,19 +14,97 @@ end
 complete -c glitch -n "__fish_using_command glitch" -s h -l help -d 'Prints help information'
 complete -c glitch -n "__fish_using_command glitch" -s V -l version -d 'Prints version information'
 complete -c glitch -n "__fish_using_command glitch" -f -a "render" -d 'Apply a glitch effect to images'
+complete -c glitch -n "__fish_using_command glitch" -f -a "recipe" -d 'Manage recipe files'
+complete -c glitch -n "__fish_using_command glitch" -f -a "presets" -d 'Manage built-in presets'
+complete -c glitch -n "__fish_using_command glitch" -f -a "effects" -d 'List available effects'
 complete -c glitch -n "__fish_using_command glitch" -f -a "completion" -d 'Generate completion scripts'
 complete -c glitch -n "__fish_using_command glitch" -f -a "help" -d 'Prints this message or the help of the given subcommand(s)'
 complete -c glitch -n "__fish_using_command glitch render" -s n -l number -d 'Number of images to generate. If generating multiple images, they will form a continuous animation'
+complete -c glitch -n "__fish_using_command glitch render" -l format -d 'Output format. \'png\' writes one image per frame, \'gif\', \'apng\' and \'webp\' a single animation. Chosen from the output file extension if not given' -r -f -a "png gif apng webp"
+complete -c glitch -n "__fish_using_command glitch render" -s o -l output -d 'Output path. May contain {stem}, {frame}, {frame:04}, {seed}, {preset} and {ext}, e.g. \'out/{stem}_{frame:04}.png\''
+complete -c glitch -n "__fish_using_command glitch render" -l frame-delay -d 'Display time of each frame of an animation in milliseconds'
+complete -c glitch -n "__fish_using_command glitch render" -l repeat -d 'Number of times an animation repeats after playing once'
+complete -c glitch -n "__fish_using_command glitch render" -l gif-speed -d 'Speed of the GIF color quantization, from 1 (best quality) to 30 (fastest)'
 complete -c glitch -n "__fish_using_command glitch render" -l color-shift -d 'Amount of offset from original position of each color channel'
+complete -c glitch -n "__fish_using_command glitch render" -l shift-mode -d 'Color shift mode. \'red-cyan\' offsets the red against the green/blue channels horizontally, \'rgb\' moves each channel independently in 2D' -r -f -a "red-cyan rgb"
+complete -c glitch -n "__fish_using_command glitch render" -l shift-offset -d 'Distance of the channels from their original position in rgb shift mode, shared or per channel'
+complete -c glitch -n "__fish_using_command glitch render" -l shift-radius -d 'How far channels drift around their offset during an animation in rgb shift mode, shared or per channel'
+complete -c glitch -n "__fish_using_command glitch render" -l shift-max-move -d 'Maximum distance a channel moves between two frames in rgb shift mode, shared or per channel'
 complete -c glitch -n "__fish_using_command glitch render" -l scan-height -d 'Height of each scanline'
 complete -c glitch -n "__fish_using_command glitch render" -l scan-gap -d 'Height of the gap between scanlines'
 complete -c glitch -n "__fish_using_command glitch render" -l desync-amp -d 'Amplitude for the desync effect'
 complete -c glitch -n "__fish_using_command glitch render" -l desync-freq -d 'Frequency for the desync effect'
 complete -c glitch -n "__fish_using_command glitch render" -l wind-onset -d 'Onset chance for wind effect'
 complete -c glitch -n "__fish_using_command glitch render" -l wind-continue -d 'Continue chance for wind effect'
+complete -c glitch -n "__fish_using_command glitch render" -l wind-direction -d 'Direction the wind streaks extend in: left, right, up, down or an angle in degrees counterclockwise from right'
+complete -c glitch -n "__fish_using_command glitch render" -l wind-fade -d 'Length in pixels over which wind streaks fade out. 0 disables fading'
+complete -c glitch -n "__fish_using_command glitch render" -l wind-fade-mode -d 'Let fading wind streaks become transparent or blend with the pixels they cover' -r -f -a "alpha blend"
 complete -c glitch -n "__fish_using_command glitch render" -l blocks -d 'Number of blocks to shift'
+complete -c glitch -n "__fish_using_command glitch render" -l grain-alpha -d 'Strength of the film grain'
+complete -c glitch -n "__fish_using_command glitch render" -l grain-frequency -d 'Frequency of the film grain. Higher values result in finer grain'
+complete -c glitch -n "__fish_using_command glitch render" -l grain-octaves -d 'Number of noise layers of the film grain'
+complete -c glitch -n "__fish_using_command glitch render" -l grain-persistence -d 'Strength of each noise layer of the film grain relative to the previous one'
+complete -c glitch -n "__fish_using_command glitch render" -l grain-lacunarity -d 'Frequency of each noise layer of the film grain relative to the previous one'
+complete -c glitch -n "__fish_using_command glitch render" -l grain-color -d 'Use the same grain for all color channels or separate grain per channel' -r -f -a "mono rgb"
+complete -c glitch -n "__fish_using_command glitch render" -l grain-blend -d 'Brighten the image by the grain or overlay it, darkening dark and brightening light areas' -r -f -a "add overlay"
+complete -c glitch -n "__fish_using_command glitch render" -l grain-motion -d 'Fresh grain every frame of an animation, or grain flowing smoothly between frames' -r -f -a "reseed coherent"
+complete -c glitch -n "__fish_using_command glitch render" -l grain-speed -d 'Distance the grain moves through the noise each frame with coherent motion'
+complete -c glitch -n "__fish_using_command glitch render" -l preset -d 'Start from a built-in set of options. Options given on the command line take precedence' -r -f -a "subtle vhs cyberkitten cyberpunk datamosh-lite"
+complete -c glitch -n "__fish_using_command glitch render" -l recipe -d 'Load options and pipeline from a TOML or JSON recipe file. Options given on the command line take precedence'
+complete -c glitch -n "__fish_using_command glitch render" -l pipeline -d 'Comma-separated list of effects to apply, in order. Effects may be repeated and take parameters, e.g. \'wind(onset=0.2,continue=0.5)\''
+complete -c glitch -n "__fish_using_command glitch render" -l pipeline-file -d 'Read the pipeline spec from a file, one effect per line. Takes precedence over --pipeline'
+complete -c glitch -n "__fish_using_command glitch render" -l keyframe -d 'Animate an effect parameter across frames, e.g. \'color-shift.distance=0@0,12@30\'. Append \':ease\' or \':step\' to the parameter to change the interpolation. Can be given multiple times'
+complete -c glitch -n "__fish_using_command glitch render" -l seed -d 'Seed for the random number generator. Rendering the same input with the same seed produces identical images'
+complete -c glitch -n "__fish_using_command glitch render" -l loop -d 'Make the animation loop seamlessly, so the frame after the last one would equal the first'
 complete -c glitch -n "__fish_using_command glitch render" -s h -l help -d 'Prints help information'
 complete -c glitch -n "__fish_using_command glitch render" -s V -l version -d 'Prints version information'
+complete -c glitch -n "__fish_using_command glitch recipe" -s h -l help -d 'Prints help information'
+complete -c glitch -n "__fish_using_command glitch recipe" -s V -l version -d 'Prints version information'
+complete -c glitch -n "__fish_using_command glitch recipe" -f -a "dump" -d 'Write the given options as a recipe'
+complete -c glitch -n "__fish_using_command glitch recipe" -f -a "help" -d 'Prints this message or the help of the given subcommand(s)'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l color-shift -d 'Amount of offset from original position of each color channel'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l shift-mode -d 'Color shift mode. \'red-cyan\' offsets the red against the green/blue channels horizontally, \'rgb\' moves each channel independently in 2D' -r -f -a "red-cyan rgb"
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l shift-offset -d 'Distance of the channels from their original position in rgb shift mode, shared or per channel'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l shift-radius -d 'How far channels drift around their offset during an animation in rgb shift mode, shared or per channel'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l shift-max-move -d 'Maximum distance a channel moves between two frames in rgb shift mode, shared or per channel'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l scan-height -d 'Height of each scanline'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l scan-gap -d 'Height of the gap between scanlines'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l desync-amp -d 'Amplitude for the desync effect'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l desync-freq -d 'Frequency for the desync effect'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l wind-onset -d 'Onset chance for wind effect'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l wind-continue -d 'Continue chance for wind effect'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l wind-direction -d 'Direction the wind streaks extend in: left, right, up, down or an angle in degrees counterclockwise from right'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l wind-fade -d 'Length in pixels over which wind streaks fade out. 0 disables fading'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l wind-fade-mode -d 'Let fading wind streaks become transparent or blend with the pixels they cover' -r -f -a "alpha blend"
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l blocks -d 'Number of blocks to shift'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l grain-alpha -d 'Strength of the film grain'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l grain-frequency -d 'Frequency of the film grain. Higher values result in finer grain'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l grain-octaves -d 'Number of noise layers of the film grain'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l grain-persistence -d 'Strength of each noise layer of the film grain relative to the previous one'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l grain-lacunarity -d 'Frequency of each noise layer of the film grain relative to the previous one'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l grain-color -d 'Use the same grain for all color channels or separate grain per channel' -r -f -a "mono rgb"
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l grain-blend -d 'Brighten the image by the grain or overlay it, darkening dark and brightening light areas' -r -f -a "add overlay"
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l grain-motion -d 'Fresh grain every frame of an animation, or grain flowing smoothly between frames' -r -f -a "reseed coherent"
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l grain-speed -d 'Distance the grain moves through the noise each frame with coherent motion'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l preset -d 'Start from a built-in set of options. Options given on the command line take precedence' -r -f -a "subtle vhs cyberkitten cyberpunk datamosh-lite"
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l recipe -d 'Load options and pipeline from a TOML or JSON recipe file. Options given on the command line take precedence'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l pipeline -d 'Comma-separated list of effects to apply, in order. Effects may be repeated and take parameters, e.g. \'wind(onset=0.2,continue=0.5)\''
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l pipeline-file -d 'Read the pipeline spec from a file, one effect per line. Takes precedence over --pipeline'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -l keyframe -d 'Animate an effect parameter across frames, e.g. \'color-shift.distance=0@0,12@30\'. Append \':ease\' or \':step\' to the parameter to change the interpolation. Can be given multiple times'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -s h -l help -d 'Prints help information'
+complete -c glitch -n "__fish_using_command glitch recipe dump" -s V -l version -d 'Prints version information'
+complete -c glitch -n "__fish_using_command glitch recipe help" -s h -l help -d 'Prints help information'
+complete -c glitch -n "__fish_using_command glitch recipe help" -s V -l version -d 'Prints version information'
+complete -c glitch -n "__fish_using_command glitch presets" -s h -l help -d 'Prints help information'
+complete -c glitch -n "__fish_using_command glitch presets" -s V -l version -d 'Prints version information'
+complete -c glitch -n "__fish_using_command glitch presets" -f -a "list" -d 'List built-in presets and their options'
+complete -c glitch -n "__fish_using_command glitch presets" -f -a "help" -d 'Prints this message or the help of the given subcommand(s)'
+complete -c glitch -n "__fish_using_command glitch presets list" -s h -l help -d 'Prints help information'
+complete -c glitch -n "__fish_using_command glitch presets list" -s V -l version -d 'Prints version information'
+complete -c glitch -n "__fish_using_command glitch presets help" -s h -l help -d 'Prints help information'
+complete -c glitch -n "__fish_using_command glitch presets help" -s V -l version -d 'Prints version information'
+complete -c glitch -n "__fish_using_command glitch effects" -s h -l help -d 'Prints help information'
+complete -c glitch -n "__fish_using_command glitch effects" -s V -l version -d 'Prints version information'
 complete -c glitch -n "__fish_using_command glitch completion" -l zsh -d 'Generate zsh completion'
 complete -c glitch -n "__fish_using_command glitch completion" -l bash -d 'Generate bash completion'
 complete -c glitch -n "__fish_using_command glitch completion" -l fish -d 'Generate fish completion'
@@ -0,0 +1,192 @@
+use image::{ImageBuffer, Pixel};
+use rand::Rng;
+use std::str::FromStr;
+use effects::{Channels, Effect, Params, parse_param, unknown_param};
+use util::{GlitchRng, RgbaBuf, RgbaImage};
+
+/// Options for the bit depth reduction
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct CrushOptions {
+    /// Number of bits kept per channel, from 1 to 8
+    pub bits: u8,
+    pub channels: Channels,
+}
+
+impl Default for CrushOptions {
+    fn default() -> CrushOptions {
+        CrushOptions {
+            bits: 3,
+            channels: Channels::all(),
+        }
+    }
+}
+
+impl Params for CrushOptions {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "bits" => self.bits = parse_param(key, value)?,
+            "channels" => self.channels = parse_param(key, value)?,
+            _ => return unknown_param(key),
+        }
+        Ok(())
+    }
+}
+
+impl Effect for CrushOptions {
+    fn apply(&self, img: &RgbaBuf, _: &mut GlitchRng) -> RgbaBuf {
+        crush(img, self)
+    }
+
+    fn step(&self, _: &mut GlitchRng) -> Box<dyn Effect> {
+        Box::new(*self)
+    }
+}
+
+/// What happens to the selected bit-planes
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BitPlaneOperation {
+    /// Invert the bits
+    Xor,
+    /// Move the bits sideways
+    Shift,
+    /// Clear the bits
+    Zero,
+}
+
+impl FromStr for BitPlaneOperation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BitPlaneOperation, String> {
+        match s {
+            "xor" => Ok(BitPlaneOperation::Xor),
+            "shift" => Ok(BitPlaneOperation::Shift),
+            "zero" => Ok(BitPlaneOperation::Zero),
+            _ => Err(format!("Unknown bit-plane operation '{}', expected xor, shift or zero", s)),
+        }
+    }
+}
+
+/// Options for the bit-plane corruption
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct BitPlaneOptions {
+    pub operation: BitPlaneOperation,
+    pub channels: Channels,
+    /// Lowest and highest affected bit-plane, 0 being the least significant bit
+    pub lowest: u8,
+    pub highest: u8,
+    /// Number of bands per frame
+    pub bands: usize,
+    /// Maximum height of a band
+    pub height: u32,
+    /// Maximum horizontal distance bits are moved by the shift operation
+    pub shift: u32,
+}
+
+impl Default for BitPlaneOptions {
+    fn default() -> BitPlaneOptions {
+        BitPlaneOptions {
+            operation: BitPlaneOperation::Xor,
+            channels: Channels::all(),
+            lowest: 5,
+            highest: 7,
+            bands: 6,
+            height: 24,
+            shift: 16,
+        }
+    }
+}
+
+impl Params for BitPlaneOptions {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "operation" => self.operation = parse_param(key, value)?,
+            "channels" => self.channels = parse_param(key, value)?,
+            "lowest" => self.lowest = parse_param(key, value)?,
+            "highest" => self.highest = parse_param(key, value)?,
+            "bands" => self.bands = parse_param(key, value)?,
+            "height" => self.height = parse_param(key, value)?,
+            "shift" => self.shift = parse_param(key, value)?,
+            _ => return unknown_param(key),
+        }
+        Ok(())
+    }
+}
+
+impl Effect for BitPlaneOptions {
+    fn apply(&self, img: &RgbaBuf, rng: &mut GlitchRng) -> RgbaBuf {
+        corrupt_bit_planes(img, self, rng)
+    }
+
+    fn step(&self, _: &mut GlitchRng) -> Box<dyn Effect> {
+        Box::new(*self)
+    }
+}
+
+fn crush<T: RgbaImage>(img: &T, config: &CrushOptions) -> RgbaBuf {
+    println!("* Crushing bit depth");
+
+    let bits = u8::min(8, u8::max(1, config.bits));
+    let levels = (1u32 << bits) - 1;
+
+    ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        let mut px = img.get_pixel(x, y);
+        for (channel, value) in px.channels_mut().iter_mut().take(3).enumerate() {
+            if config.channels.contains(channel) {
+                // Spread the remaining levels over the full range, so white stays white
+                let level = *value as u32 >> (8 - bits);
+                *value = (level * u8::MAX as u32 / levels) as u8;
+            }
+        }
+        px
+    })
+}
+
+fn corrupt_bit_planes<R: Rng>(img: &RgbaBuf, config: &BitPlaneOptions, rng: &mut R) -> RgbaBuf {
+    println!("* Corrupting bit-planes");
+
+    let mut output = img.clone();
+    let (width, height) = img.dimensions();
+    let lowest = u8::min(7, config.lowest);
+    let highest = u8::min(7, u8::max(lowest, config.highest));
+    let mask = ((0xFFu16 << lowest) & (0xFFu16 >> (7 - highest))) as u8;
+    if height == 0 || config.height == 0 {
+        return output;
+    }
+
+    for _ in 0..config.bands {
+        let band_height = rng.gen_range(1, config.height + 1);
+        let min_y = rng.gen_range(0, height);
+        let max_y = u32::min(height, min_y + band_height);
+
+        for channel in (0..3).filter(|&channel| config.channels.contains(channel)) {
+            let shift = if config.shift > 0 {
+                rng.gen_range(-(config.shift as i64), config.shift as i64 + 1)
+            } else {
+                0
+            };
+
+            for y in min_y..max_y {
+                for x in 0..width {
+                    let value = img.get_pixel(x, y).channels()[channel];
+                    let corrupted = match config.operation {
+                        BitPlaneOperation::Xor => value ^ mask,
+                        BitPlaneOperation::Zero => value & !mask,
+                        BitPlaneOperation::Shift => {
+                            let source_x = (x as i64 + shift).rem_euclid(width as i64) as u32;
+                            let source = img.get_pixel(source_x, y).channels()[channel];
+                            (value & !mask) | (source & mask)
+                        },
+                    };
+                    output.get_pixel_mut(x, y).channels_mut()[channel] = corrupted;
+                }
+            }
+        }
+    }
+
+    output
+}
@@ -0,0 +1,264 @@
+use image::{ImageBuffer, Pixel};
+use rand::Rng;
+use std::str::FromStr;
+use effects::{Effect, Params, RenderInfo, parse_param, unknown_param};
+use util::{GlitchRng, RgbaBuf, RgbaImage, clamping_add, seeded_rng};
+
+/// What happens to pixels moved in from outside the image
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockEdge {
+    /// Repeat the pixels at the edge
+    Clamp,
+    /// Take the pixels from the opposite side
+    Wrap,
+}
+
+impl FromStr for BlockEdge {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BlockEdge, String> {
+        match s {
+            "clamp" => Ok(BlockEdge::Clamp),
+            "wrap" => Ok(BlockEdge::Wrap),
+            _ => Err(format!("Unknown block edge '{}', expected clamp or wrap", s)),
+        }
+    }
+}
+
+/// Options for the block movement effect
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct BlockShiftOptions {
+    pub count: usize,
+    /// Maximum horizontal distance a block is moved
+    pub shift: u32,
+    /// Minimum horizontal distance a block is moved
+    pub min_shift: u32,
+    /// Maximum vertical distance a block is moved
+    pub vertical: u32,
+    /// Minimum and maximum height of a block. A maximum of 0 lets blocks reach the next one.
+    pub min_height: u32,
+    pub max_height: u32,
+    /// Minimum and maximum width of a block. A maximum of 0 makes blocks span the whole width.
+    pub min_width: u32,
+    pub max_width: u32,
+    /// Chance that a block only moves one random color channel
+    pub split: f64,
+    pub edge: BlockEdge,
+}
+
+impl BlockShiftOptions {
+    pub fn new(count: usize) -> BlockShiftOptions {
+        BlockShiftOptions {
+            count,
+            shift: 20,
+            min_shift: 3,
+            vertical: 0,
+            min_height: 16,
+            max_height: 0,
+            min_width: 16,
+            max_width: 0,
+            split: 0.0,
+            edge: BlockEdge::Clamp,
+        }
+    }
+}
+
+impl Default for BlockShiftOptions {
+    fn default() -> BlockShiftOptions {
+        BlockShiftOptions::new(5)
+    }
+}
+
+impl Params for BlockShiftOptions {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "count" => self.count = parse_param(key, value)?,
+            "shift" => self.shift = parse_param(key, value)?,
+            "min-shift" => self.min_shift = parse_param(key, value)?,
+            "vertical" => self.vertical = parse_param(key, value)?,
+            "min-height" => self.min_height = parse_param(key, value)?,
+            "max-height" => self.max_height = parse_param(key, value)?,
+            "min-width" => self.min_width = parse_param(key, value)?,
+            "max-width" => self.max_width = parse_param(key, value)?,
+            "split" => self.split = parse_param(key, value)?,
+            "edge" => self.edge = parse_param(key, value)?,
+            _ => return unknown_param(key),
+        }
+        Ok(())
+    }
+}
+
+/// Lines the blocks move down per frame
+const BLOCK_SPEED: u32 = 3;
+
+/// The block movement effect
+#[derive(Clone)]
+pub struct BlockShift {
+    options: BlockShiftOptions,
+    /// The options the blocks were placed with
+    built_from: BlockShiftOptions,
+    info: RenderInfo,
+    seed: u32,
+    frame: usize,
+    blocks: Vec<Block>
+}
+
+#[derive(Copy, Clone)]
+struct Block {
+    min_y: u32,
+    height: u32,
+    min_x: u32,
+    width: u32,
+    shift: i32,
+    shift_y: i32,
+    /// The only channel moved, if not all of them
+    channel: Option<usize>,
+}
+
+/// Random integer from `low` to `high`. Exactly one value is drawn even for empty ranges, so
+/// changing one option leaves the random choices of all others as they were.
+fn pick<R: Rng>(rng: &mut R, low: i64, high: i64) -> i64 {
+    let value = rng.next_f64();
+    if high <= low { low } else { low + (value * (high - low + 1) as f64) as i64 }
+}
+
+/// Place blocks as the seed determines for the given options
+fn place_blocks(options: &BlockShiftOptions, info: &RenderInfo, seed: u32) -> Vec<Block> {
+    let mut rng = seeded_rng(seed as u64);
+    let max_y = info.height;
+    let num_blocks = options.count;
+    let mut start_lines = vec!();
+    for _ in 0..num_blocks {
+        start_lines.push(pick(&mut rng, 0, max_y as i64 - 1) as u32);
+    }
+    start_lines.sort();
+    let mut blocks = vec!();
+    for i in 0..num_blocks {
+        let min_y = start_lines[i];
+        let max_end = if i == num_blocks - 1 { max_y } else { start_lines[i + 1] };
+        let height = pick(&mut rng, 0, max_end as i64 - min_y as i64 - 1) as u32;
+        let height = u32::max(options.min_height, height);
+        let height = if options.max_height > 0 { u32::min(options.max_height, height) } else { height };
+
+        // Small shifts are hardly visible, so push them out to the minimum distance, which can't
+        // exceed the maximum so blocks can be moved only vertically
+        let min_shift = u32::min(options.min_shift, options.shift) as i32;
+        let shift = pick(&mut rng, -(options.shift as i64), options.shift as i64) as i32;
+        let shift = if (-min_shift..0).contains(&shift) { -min_shift } else if (0..min_shift).contains(&shift) { min_shift } else { shift };
+        let shift_y = pick(&mut rng, -(options.vertical as i64), options.vertical as i64) as i32;
+
+        let max_width = u32::min(info.width, options.max_width);
+        let min_width = u32::min(max_width, options.min_width);
+        let width = pick(&mut rng, min_width as i64, max_width as i64) as u32;
+        let min_x = pick(&mut rng, 0, info.width as i64 - width as i64) as u32;
+        let (min_x, width) = if options.max_width > 0 { (min_x, width) } else { (0, info.width) };
+
+        let split = rng.next_f64() < options.split;
+        let channel = pick(&mut rng, 0, 2) as usize;
+        let channel = if split { Some(channel) } else { None };
+
+        blocks.push(Block {
+            min_y,
+            height,
+            min_x,
+            width,
+            shift,
+            shift_y,
+            channel,
+        })
+    }
+    blocks
+}
+
+impl BlockShift {
+    pub fn random<R: Rng>(options: &BlockShiftOptions, info: &RenderInfo, rng: &mut R) -> BlockShift {
+        let seed = rng.next_u32();
+        BlockShift {
+            options: *options,
+            built_from: *options,
+            info: *info,
+            seed,
+            frame: 0,
+            blocks: place_blocks(options, info, seed),
+        }
+    }
+
+    /// Distance the blocks have moved down since the first frame
+    fn displacement(&self) -> u32 {
+        let max_y = self.info.height as u64;
+        match self.info.period {
+            // Wrap around the image the whole number of times per period closest to the speed of
+            // unlooped animations
+            Some(period) => {
+                let wraps = (BLOCK_SPEED as f64 * period as f64 / max_y as f64).round() as u64;
+                (self.frame as u64 * wraps * max_y / period as u64 % max_y) as u32
+            },
+            None => BLOCK_SPEED * self.frame as u32,
+        }
+    }
+}
+
+impl Params for BlockShift {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        // Changed options take effect with the next step
+        self.options.set_param(key, value)
+    }
+}
+
+impl Effect for BlockShift {
+    fn apply(&self, img: &RgbaBuf, _: &mut GlitchRng) -> RgbaBuf {
+        offset_blocks(img, self)
+    }
+
+    fn step(&self, _: &mut GlitchRng) -> Box<dyn Effect> {
+        let mut next = self.clone();
+        if self.options != self.built_from {
+            // The same seed keeps the blocks in place, so only the changed options are visible
+            next.blocks = place_blocks(&self.options, &self.info, self.seed);
+            next.built_from = self.options;
+        }
+        next.frame = self.frame + 1;
+        Box::new(next)
+    }
+}
+
+fn offset_blocks<T: RgbaImage>(img: &T, config: &BlockShift) -> RgbaBuf {
+    println!("* Shifting blocks");
+
+    let displacement = config.displacement();
+    let max_y = config.info.height;
+    let (width, height) = (img.width(), img.height());
+    let source = |position: u32, shift: i32, size: u32| match config.options.edge {
+        BlockEdge::Clamp => clamping_add(position, shift, size - 1),
+        BlockEdge::Wrap => (position as i64 + shift as i64).rem_euclid(size as i64) as u32,
+    };
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        for block in &config.blocks {
+            let min_y = block.min_y + displacement;
+            let in_block = if config.info.period.is_some() {
+                // Blocks leaving the image at the bottom re-enter at the top
+                (y + max_y - min_y % max_y) % max_y < block.height
+            } else {
+                if y < min_y { break; }
+                y < min_y + block.height
+            };
+            if in_block && x >= block.min_x && x < block.min_x + block.width {
+                let moved = img.get_pixel(source(x, block.shift, width), source(y, block.shift_y, height));
+                return match block.channel {
+                    Some(channel) => {
+                        let mut px = img.get_pixel(x, y);
+                        px.channels_mut()[channel] = moved.channels()[channel];
+                        px
+                    },
+                    None => moved,
+                };
+            }
+        }
+
+        img.get_pixel(x, y)
+    })
+}
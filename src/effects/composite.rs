@@ -0,0 +1,209 @@
+use image::{ImageBuffer, Rgba, Pixel};
+use rand::Rng;
+use std::f64::consts::PI;
+use std::str::FromStr;
+use effects::{Effect, Params, RenderInfo, parse_param, unknown_param};
+use util::{GlitchRng, RgbaBuf, RgbaImage, rgb_to_yiq, rgb_to_yuv, yiq_to_rgb, yuv_to_rgb};
+
+/// The analog TV standard that is simulated
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TvStandard {
+    /// YIQ color with the subcarrier phase alternating between lines
+    Ntsc,
+    /// YUV color with the V phase flipped every line and chroma averaged over two lines
+    Pal,
+}
+
+impl FromStr for TvStandard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TvStandard, String> {
+        match s {
+            "ntsc" => Ok(TvStandard::Ntsc),
+            "pal" => Ok(TvStandard::Pal),
+            _ => Err(format!("Unknown TV standard '{}', expected ntsc or pal", s)),
+        }
+    }
+}
+
+/// Options for the composite video effect
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct CompositeOptions {
+    pub standard: TvStandard,
+    /// Width in pixels the chroma is averaged over, limiting its horizontal resolution
+    pub chroma_blur: u32,
+    /// Amount of chroma left in the luma, visible as crawling dots along colored edges
+    pub dot_crawl: f64,
+    /// Amount of fine luma detail mistaken for chroma, visible as rainbow patterns
+    pub crosstalk: f64,
+    /// Frequency of the color subcarrier in cycles per pixel
+    pub subcarrier: f64,
+    /// Subcarrier phase advance per frame in cycles
+    pub crawl_speed: f64,
+}
+
+impl Default for CompositeOptions {
+    fn default() -> CompositeOptions {
+        CompositeOptions {
+            standard: TvStandard::Ntsc,
+            chroma_blur: 6,
+            dot_crawl: 0.15,
+            crosstalk: 0.3,
+            subcarrier: 0.25,
+            crawl_speed: 0.5,
+        }
+    }
+}
+
+impl Params for CompositeOptions {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "standard" => self.standard = parse_param(key, value)?,
+            "chroma-blur" => self.chroma_blur = parse_param(key, value)?,
+            "dot-crawl" => self.dot_crawl = parse_param(key, value)?,
+            "crosstalk" => self.crosstalk = parse_param(key, value)?,
+            "subcarrier" => self.subcarrier = parse_param(key, value)?,
+            "crawl-speed" => self.crawl_speed = parse_param(key, value)?,
+            _ => return unknown_param(key),
+        }
+        Ok(())
+    }
+}
+
+/// The composite video effect
+#[derive(Copy, Clone)]
+pub struct Composite {
+    options: CompositeOptions,
+    /// Subcarrier phase of the current frame in cycles
+    phase: f64,
+    period: Option<usize>,
+}
+
+impl Composite {
+    pub fn random<R: Rng>(options: &CompositeOptions, info: &RenderInfo, rng: &mut R) -> Composite {
+        Composite {
+            options: *options,
+            phase: rng.next_f64(),
+            period: info.period,
+        }
+    }
+}
+
+impl Params for Composite {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        self.options.set_param(key, value)
+    }
+}
+
+impl Effect for Composite {
+    fn apply(&self, img: &RgbaBuf, _: &mut GlitchRng) -> RgbaBuf {
+        composite(img, self)
+    }
+
+    fn step(&self, _: &mut GlitchRng) -> Box<dyn Effect> {
+        let phase_step = match self.period {
+            // Complete a whole number of cycles per period
+            Some(period) => (self.options.crawl_speed * period as f64).round() / period as f64,
+            None => self.options.crawl_speed,
+        };
+        Box::new(Composite {
+            phase: (self.phase + phase_step).fract(),
+            ..*self
+        })
+    }
+}
+
+fn composite<T: RgbaImage>(img: &T, config: &Composite) -> RgbaBuf {
+    println!("* Encoding composite video");
+
+    let opts = &config.options;
+    let (width, height) = (img.width(), img.height());
+    let max = u8::MAX as f64;
+    let mut output = ImageBuffer::new(width, height);
+    let mut previous_chroma: Option<Vec<(f64, f64)>> = None;
+
+    for y in 0..height {
+        let line = (0..width)
+            .map(|x| {
+                let (r, g, b, _) = img.get_pixel(x, y).channels4();
+                let (r, g, b) = (r as f64 / max, g as f64 / max, b as f64 / max);
+                match opts.standard {
+                    TvStandard::Ntsc => rgb_to_yiq(r, g, b),
+                    TvStandard::Pal => rgb_to_yuv(r, g, b),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Phase of the subcarrier at each pixel of this line
+        let (line_phase, v_sign) = match opts.standard {
+            TvStandard::Ntsc => (0.5 * y as f64, 1.0),
+            TvStandard::Pal => (0.25 * y as f64, if y % 2 == 0 { 1.0 } else { -1.0 }),
+        };
+        let carrier = |x: usize| 2.0 * PI * (opts.subcarrier * x as f64 + line_phase + config.phase);
+
+        // Luma detail close to the subcarrier frequency, which the decoder can't tell apart from chroma
+        let luma_detail = (0..line.len())
+            .map(|x| {
+                let left = line[x.saturating_sub(1)].0;
+                let right = line[usize::min(x + 1, line.len() - 1)].0;
+                line[x].0 - (left + 2.0 * line[x].0 + right) / 4.0
+            })
+            .collect::<Vec<_>>();
+
+        let chroma = line.iter().enumerate()
+            .map(|(x, &(_, c1, c2))| {
+                let theta = carrier(x);
+                let leak = opts.crosstalk * luma_detail[x] * 2.0;
+                (c1 + leak * f64::cos(theta), c2 + leak * f64::sin(theta) * v_sign)
+            })
+            .collect::<Vec<_>>();
+        let chroma = band_limit(&chroma, opts.chroma_blur as usize);
+
+        // The PAL delay line averages chroma with the previous line, canceling phase errors
+        let decoded_chroma = match (opts.standard, &previous_chroma) {
+            (TvStandard::Pal, Some(previous)) => chroma.iter().zip(previous)
+                .map(|(&(u, v), &(pu, pv))| ((u + pu) / 2.0, (v + pv) / 2.0))
+                .collect(),
+            _ => chroma.clone(),
+        };
+
+        for (x, &(luma, c1, c2)) in line.iter().enumerate() {
+            let theta = carrier(x);
+            let luma = luma + opts.dot_crawl * (c1 * f64::cos(theta) + c2 * f64::sin(theta) * v_sign);
+            let (c1, c2) = decoded_chroma[x];
+            let (r, g, b) = match opts.standard {
+                TvStandard::Ntsc => yiq_to_rgb(luma, c1, c2),
+                TvStandard::Pal => yuv_to_rgb(luma, c1, c2),
+            };
+            let a = img.get_pixel(x as u32, y).channels4().3;
+            output.put_pixel(x as u32, y, Rgba::from_channels((r * max).round() as u8, (g * max).round() as u8, (b * max).round() as u8, a));
+        }
+
+        previous_chroma = Some(chroma);
+    }
+
+    output
+}
+
+/// Average the chroma of each pixel with its neighbors over the given width
+fn band_limit(chroma: &[(f64, f64)], width: usize) -> Vec<(f64, f64)> {
+    if width <= 1 {
+        return chroma.to_vec();
+    }
+
+    let before = width / 2;
+    let after = width - before - 1;
+    (0..chroma.len())
+        .map(|x| {
+            let start = x.saturating_sub(before);
+            let end = usize::min(chroma.len(), x + after + 1);
+            let (sum1, sum2) = chroma[start..end].iter()
+                .fold((0.0, 0.0), |(s1, s2), &(c1, c2)| (s1 + c1, s2 + c2));
+            let count = (end - start) as f64;
+            (sum1 / count, sum2 / count)
+        })
+        .collect()
+}
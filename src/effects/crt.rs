@@ -0,0 +1,192 @@
+use image::{ImageBuffer, Rgba, Pixel};
+use std::str::FromStr;
+use effects::{Effect, Params, parse_param, unknown_param};
+use util::{GlitchRng, RgbaBuf, RgbaImage, box_blur};
+
+/// Arrangement of the red, green and blue phosphors
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CrtMask {
+    None,
+    /// Continuous vertical stripes
+    Grille,
+    /// Dots, shifted on every other line
+    Shadow,
+    /// Vertical stripes interrupted by staggered gaps
+    Slot,
+}
+
+impl FromStr for CrtMask {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CrtMask, String> {
+        match s {
+            "none" => Ok(CrtMask::None),
+            "grille" => Ok(CrtMask::Grille),
+            "shadow" => Ok(CrtMask::Shadow),
+            "slot" => Ok(CrtMask::Slot),
+            _ => Err(format!("Unknown CRT mask '{}', expected none, grille, shadow or slot", s)),
+        }
+    }
+}
+
+impl CrtMask {
+    /// Brightness of a color channel at the given pixel
+    fn weight(&self, x: u32, y: u32, channel: usize, strength: f64) -> f64 {
+        let (phosphor, lit) = match *self {
+            CrtMask::None => return 1.0,
+            CrtMask::Grille => (x % 3, true),
+            CrtMask::Shadow => ((x + y % 2) % 3, true),
+            // Every triad column has a dark line every 4 lines, offset against its neighbors
+            CrtMask::Slot => (x % 3, !(y + 2 * ((x / 3) % 2)).is_multiple_of(4)),
+        };
+        if lit && phosphor as usize == channel {
+            1.0
+        } else {
+            1.0 - strength
+        }
+    }
+}
+
+/// Options for the CRT display effect
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct CrtOptions {
+    /// Strength of the barrel distortion of the curved screen
+    pub curvature: f64,
+    pub mask: CrtMask,
+    /// How much light the mask blocks between phosphors, from 0 to 1
+    pub mask_strength: f64,
+    /// Strength of the glow around bright areas
+    pub bloom: f64,
+    /// Size of the glow around bright areas in pixels
+    pub bloom_radius: u32,
+    /// Darkening towards the corners, from 0 to 1
+    pub vignette: f64,
+    /// Factor the image is enlarged by, so the mask is visible at the target resolution
+    pub scale: u32,
+}
+
+impl Default for CrtOptions {
+    fn default() -> CrtOptions {
+        CrtOptions {
+            curvature: 0.08,
+            mask: CrtMask::Grille,
+            mask_strength: 0.3,
+            bloom: 0.4,
+            bloom_radius: 4,
+            vignette: 0.4,
+            scale: 1,
+        }
+    }
+}
+
+impl Params for CrtOptions {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "curvature" => self.curvature = parse_param(key, value)?,
+            "mask" => self.mask = parse_param(key, value)?,
+            "mask-strength" => self.mask_strength = parse_param(key, value)?,
+            "bloom" => self.bloom = parse_param(key, value)?,
+            "bloom-radius" => self.bloom_radius = parse_param(key, value)?,
+            "vignette" => self.vignette = parse_param(key, value)?,
+            "scale" => self.scale = parse_param(key, value)?,
+            _ => return unknown_param(key),
+        }
+        Ok(())
+    }
+}
+
+impl Effect for CrtOptions {
+    fn apply(&self, img: &RgbaBuf, _: &mut GlitchRng) -> RgbaBuf {
+        crt(img, self)
+    }
+
+    fn step(&self, _: &mut GlitchRng) -> Box<dyn Effect> {
+        Box::new(*self)
+    }
+}
+
+/// Brightness above which phosphors start to glow
+const BLOOM_THRESHOLD: f64 = 0.6;
+
+fn crt<T: RgbaImage>(img: &T, config: &CrtOptions) -> RgbaBuf {
+    println!("* Displaying on CRT");
+
+    let scale = u32::max(1, config.scale);
+    let (width, height) = (img.width() * scale, img.height() * scale);
+    let max = u8::MAX as f64;
+
+    // The picture as projected onto the curved screen, as channel values between 0 and 1
+    let mut screen = vec![[0.0; 4]; (width * height) as usize];
+    for y in 0..height {
+        for x in 0..width {
+            // Coordinates relative to the center, from -1 to 1
+            let u = (x as f64 + 0.5) / width as f64 * 2.0 - 1.0;
+            let v = (y as f64 + 0.5) / height as f64 * 2.0 - 1.0;
+            let distortion = 1.0 + config.curvature * (u * u + v * v);
+            let (u, v) = (u * distortion, v * distortion);
+            if u.abs() > 1.0 || v.abs() > 1.0 {
+                // Outside of the screen
+                screen[(y * width + x) as usize] = [0.0, 0.0, 0.0, 1.0];
+                continue;
+            }
+
+            let source_x = (u + 1.0) / 2.0 * img.width() as f64 - 0.5;
+            let source_y = (v + 1.0) / 2.0 * img.height() as f64 - 0.5;
+            screen[(y * width + x) as usize] = sample(img, source_x, source_y);
+        }
+    }
+
+    // Blurred highlights of each color channel
+    let glow = if config.bloom > 0.0 && config.bloom_radius > 0 {
+        let glow = (0..3)
+            .map(|channel| {
+                let highlights = screen.iter()
+                    .map(|px| f64::max(0.0, px[channel] - BLOOM_THRESHOLD))
+                    .collect::<Vec<_>>();
+                box_blur(&highlights, width, height, config.bloom_radius)
+            })
+            .collect::<Vec<_>>();
+        Some(glow)
+    } else {
+        None
+    };
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let i = (y * width + x) as usize;
+        let u = (x as f64 + 0.5) / width as f64 * 2.0 - 1.0;
+        let v = (y as f64 + 0.5) / height as f64 * 2.0 - 1.0;
+        let vignette = 1.0 - config.vignette * (u * u + v * v) / 2.0;
+
+        let mut channels = [0u8; 4];
+        for channel in 0..3 {
+            let mut value = screen[i][channel] * config.mask.weight(x, y, channel, config.mask_strength);
+            if let Some(ref glow) = glow {
+                value += glow[channel][i] * config.bloom;
+            }
+            channels[channel] = ((value * vignette).clamp(0.0, 1.0) * max).round() as u8;
+        }
+        channels[3] = (screen[i][3] * max).round() as u8;
+        Rgba(channels)
+    })
+}
+
+/// Bilinearly interpolated channel values between 0 and 1
+fn sample<T: RgbaImage>(img: &T, x: f64, y: f64) -> [f64; 4] {
+    let x = x.clamp(0.0, (img.width() - 1) as f64);
+    let y = y.clamp(0.0, (img.height() - 1) as f64);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = (u32::min(x0 + 1, img.width() - 1), u32::min(y0 + 1, img.height() - 1));
+    let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+
+    let mut result = [0.0; 4];
+    for &(px, py, weight) in [(x0, y0, (1.0 - fx) * (1.0 - fy)), (x1, y0, fx * (1.0 - fy)), (x0, y1, (1.0 - fx) * fy), (x1, y1, fx * fy)].iter() {
+        let channels = img.get_pixel(px, py).to_rgba();
+        for (value, &channel) in result.iter_mut().zip(channels.channels()) {
+            *value += weight * channel as f64 / u8::MAX as f64;
+        }
+    }
+    result
+}
@@ -0,0 +1,156 @@
+use image::{GenericImage, GenericImageView, Pixel};
+use std::cell::RefCell;
+use effects::{Effect, Params, parse_param, unknown_param};
+use util::{GlitchRng, RgbaBuf};
+
+/// Options for the multi-frame datamosh effect
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct DatamoshOptions {
+    /// Width and height of the blocks motion is estimated for
+    pub block: u32,
+    /// Number of frames a reference frame is held before a fresh one is taken. 0 holds the first frame forever.
+    pub hold: usize,
+    /// Maximum distance in pixels a block is searched for in the previous frame
+    pub search: u32,
+}
+
+impl Default for DatamoshOptions {
+    fn default() -> DatamoshOptions {
+        DatamoshOptions {
+            block: 16,
+            hold: 10,
+            search: 8,
+        }
+    }
+}
+
+impl Params for DatamoshOptions {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "block" => self.block = parse_param(key, value)?,
+            "hold" => self.hold = parse_param(key, value)?,
+            "search" => self.search = parse_param(key, value)?,
+            _ => return unknown_param(key),
+        }
+        Ok(())
+    }
+}
+
+/// The datamosh effect. Motion between the inputs of consecutive frames drags the pixels of a
+/// held reference frame along, like a video with its key frames removed.
+#[derive(Clone)]
+pub struct Datamosh {
+    options: DatamoshOptions,
+    frame: usize,
+    /// The input and output of the last applied frame
+    history: RefCell<Option<(RgbaBuf, RgbaBuf)>>,
+}
+
+impl Datamosh {
+    pub fn new(options: &DatamoshOptions) -> Datamosh {
+        Datamosh {
+            options: *options,
+            frame: 0,
+            history: RefCell::new(None),
+        }
+    }
+}
+
+impl Params for Datamosh {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        self.options.set_param(key, value)
+    }
+}
+
+impl Effect for Datamosh {
+    fn apply(&self, img: &RgbaBuf, _: &mut GlitchRng) -> RgbaBuf {
+        // Only the first frame is a key frame when holding forever
+        let key_frame = self.frame.is_multiple_of(self.options.hold);
+
+        let mut history = self.history.borrow_mut();
+        let output = match *history {
+            Some((ref previous, ref reference)) if !key_frame && previous.dimensions() == img.dimensions() => {
+                datamosh(img, previous, reference, &self.options)
+            },
+            _ => img.clone(),
+        };
+        *history = Some((img.clone(), output.clone()));
+        output
+    }
+
+    fn step(&self, _: &mut GlitchRng) -> Box<dyn Effect> {
+        Box::new(Datamosh {
+            frame: self.frame + 1,
+            ..self.clone()
+        })
+    }
+}
+
+fn datamosh(img: &RgbaBuf, previous: &RgbaBuf, reference: &RgbaBuf, config: &DatamoshOptions) -> RgbaBuf {
+    println!("* Datamoshing");
+
+    let block = u32::max(1, config.block);
+    let (width, height) = img.dimensions();
+    let mut output = reference.clone();
+
+    for by in (0..height).step_by(block as usize) {
+        for bx in (0..width).step_by(block as usize) {
+            let block_width = u32::min(block, width - bx);
+            let block_height = u32::min(block, height - by);
+            let (dx, dy) = motion_vector(img, previous, (bx, by), (block_width, block_height), config.search);
+
+            let source = reference.view((bx as i64 + dx) as u32, (by as i64 + dy) as u32, block_width, block_height);
+            output.copy_from(&source, bx, by).expect("Block outside of image");
+        }
+    }
+
+    output
+}
+
+/// Find where a block of the current frame was in the previous frame, as offset from its current position
+fn motion_vector(img: &RgbaBuf, previous: &RgbaBuf, (bx, by): (u32, u32), (block_width, block_height): (u32, u32), search: u32) -> (i64, i64) {
+    let (width, height) = img.dimensions();
+    let search = search as i64;
+
+    let difference = |dx: i64, dy: i64, limit: u64| {
+        let mut sum = 0;
+        for y in by..by + block_height {
+            for x in bx..bx + block_width {
+                let current = img.get_pixel(x, y).channels4();
+                let before = previous.get_pixel((x as i64 + dx) as u32, (y as i64 + dy) as u32).channels4();
+                sum += (current.0 as i64 - before.0 as i64).unsigned_abs()
+                    + (current.1 as i64 - before.1 as i64).unsigned_abs()
+                    + (current.2 as i64 - before.2 as i64).unsigned_abs();
+            }
+            // No need to continue once worse than the best match
+            if sum >= limit {
+                break;
+            }
+        }
+        sum
+    };
+
+    // Prefer no motion unless another position matches strictly better
+    let mut best = (0, 0);
+    let mut best_difference = difference(0, 0, u64::MAX);
+    for dy in -search..=search {
+        for dx in -search..=search {
+            let inside = bx as i64 + dx >= 0 && by as i64 + dy >= 0
+                && bx as i64 + dx + block_width as i64 <= width as i64
+                && by as i64 + dy + block_height as i64 <= height as i64;
+            if best_difference == 0 || !inside || (dx, dy) == (0, 0) {
+                continue;
+            }
+
+            let d = difference(dx, dy, best_difference);
+            if d < best_difference {
+                best = (dx, dy);
+                best_difference = d;
+            }
+        }
+    }
+
+    best
+}
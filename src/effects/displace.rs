@@ -0,0 +1,205 @@
+use image::{self, GrayImage, ImageBuffer};
+use noise::{self, MultiFractal, NoiseModule, Seedable};
+use rand::Rng;
+use std::f64::consts::PI;
+use std::rc::Rc;
+use std::str::FromStr;
+use effects::{Effect, Params, RenderInfo, parse_param, unknown_param};
+use util::{GlitchRng, RgbaBuf, RgbaImage};
+
+/// The noise field driving the displacement
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisplaceNoise {
+    /// Smooth, wavy distortion
+    Perlin,
+    /// Perlin noise layered over several octaves, adding finer detail
+    Fbm,
+    /// Cells moved by a constant distance each, like shattered glass
+    Worley,
+}
+
+impl FromStr for DisplaceNoise {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DisplaceNoise, String> {
+        match s {
+            "perlin" => Ok(DisplaceNoise::Perlin),
+            "fbm" => Ok(DisplaceNoise::Fbm),
+            "worley" => Ok(DisplaceNoise::Worley),
+            _ => Err(format!("Unknown displacement noise '{}', expected perlin, fbm or worley", s)),
+        }
+    }
+}
+
+/// Options for the displacement map effect
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct DisplaceOptions {
+    pub noise: DisplaceNoise,
+    /// Grayscale image used instead of noise, stretched to the size of the input
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub map: Option<String>,
+    /// Maximum horizontal and vertical displacement in pixels
+    pub amplitude_x: f64,
+    pub amplitude_y: f64,
+    /// Number of noise features across the width of the image
+    pub frequency: f64,
+    /// Number of noise layers of the fbm noise
+    pub octaves: usize,
+    /// Distance the noise moves through its third dimension each frame
+    pub speed: f64,
+}
+
+impl Default for DisplaceOptions {
+    fn default() -> DisplaceOptions {
+        DisplaceOptions {
+            noise: DisplaceNoise::Perlin,
+            map: None,
+            amplitude_x: 12.0,
+            amplitude_y: 12.0,
+            frequency: 4.0,
+            octaves: 4,
+            speed: 0.05,
+        }
+    }
+}
+
+impl Params for DisplaceOptions {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "noise" => self.noise = parse_param(key, value)?,
+            "map" => self.map = if value.is_empty() { None } else { Some(String::from(value)) },
+            "amplitude-x" => self.amplitude_x = parse_param(key, value)?,
+            "amplitude-y" => self.amplitude_y = parse_param(key, value)?,
+            "frequency" => self.frequency = parse_param(key, value)?,
+            "octaves" => self.octaves = parse_param(key, value)?,
+            "speed" => self.speed = parse_param(key, value)?,
+            _ => return unknown_param(key),
+        }
+        Ok(())
+    }
+}
+
+/// The displacement map effect
+#[derive(Clone)]
+pub struct Displace {
+    options: DisplaceOptions,
+    map: Option<Rc<GrayImage>>,
+    seed: u32,
+    frame: usize,
+    period: Option<usize>,
+}
+
+impl Displace {
+    pub fn random<R: Rng>(options: &DisplaceOptions, info: &RenderInfo, rng: &mut R) -> Result<Displace, String> {
+        Ok(Displace {
+            options: options.clone(),
+            map: load_map(&options.map)?,
+            seed: rng.next_u32(),
+            frame: 0,
+            period: info.period,
+        })
+    }
+
+    /// Position in the extra dimensions of the noise for the current frame
+    fn time(&self) -> [f64; 2] {
+        match self.period {
+            // Travel on a circle through the third and fourth dimension, so the last frame leads back to the first
+            Some(period) => {
+                let radius = self.options.speed * period as f64 / (2.0 * PI);
+                let angle = 2.0 * PI * (self.frame % period) as f64 / period as f64;
+                [radius * f64::cos(angle), radius * f64::sin(angle)]
+            },
+            None => [self.options.speed * self.frame as f64, 0.0],
+        }
+    }
+}
+
+fn load_map(path: &Option<String>) -> Result<Option<Rc<GrayImage>>, String> {
+    match *path {
+        Some(ref path) => image::open(path)
+            .map(|map| Some(Rc::new(map.to_luma8())))
+            .map_err(|e| format!("Unable to load displacement map '{}': {}", path, e)),
+        None => Ok(None),
+    }
+}
+
+impl Params for Displace {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        self.options.set_param(key, value)?;
+        if key == "map" {
+            self.map = load_map(&self.options.map)?;
+        }
+        Ok(())
+    }
+}
+
+impl Effect for Displace {
+    fn apply(&self, img: &RgbaBuf, _: &mut GlitchRng) -> RgbaBuf {
+        println!("* Displacing pixels");
+
+        let opts = &self.options;
+        let seeds = (self.seed as usize, self.seed.wrapping_add(1) as usize);
+        if let Some(ref map) = self.map {
+            return displace(img, opts, |x, y| {
+                // Mid gray stays in place
+                let value = map.get_pixel(x * map.width() / img.width(), y * map.height() / img.height())[0];
+                let value = value as f64 / u8::MAX as f64 * 2.0 - 1.0;
+                (value, value)
+            });
+        }
+
+        let time = self.time();
+        match opts.noise {
+            DisplaceNoise::Perlin => {
+                let noises = (noise::Perlin::new().set_seed(seeds.0), noise::Perlin::new().set_seed(seeds.1));
+                displace_by_noise(img, self, &noises, time)
+            },
+            DisplaceNoise::Fbm => {
+                let fbm = || noise::Fbm::new().set_octaves(opts.octaves);
+                let noises = (fbm().set_seed(seeds.0), fbm().set_seed(seeds.1));
+                displace_by_noise(img, self, &noises, time)
+            },
+            DisplaceNoise::Worley => {
+                let noises = (noise::Worley::new().set_seed(seeds.0), noise::Worley::new().set_seed(seeds.1));
+                displace_by_noise(img, self, &noises, time)
+            },
+        }
+    }
+
+    fn step(&self, _: &mut GlitchRng) -> Box<dyn Effect> {
+        Box::new(Displace {
+            frame: self.frame + 1,
+            ..self.clone()
+        })
+    }
+}
+
+fn displace_by_noise<T: RgbaImage, N>(img: &T, effect: &Displace, (noise_x, noise_y): &(N, N), [t1, t2]: [f64; 2]) -> RgbaBuf
+    where N: NoiseModule<[f64; 3], Output = f64> + NoiseModule<[f64; 4], Output = f64>
+{
+    let scale = effect.options.frequency / img.width() as f64;
+    displace(img, &effect.options, |x, y| {
+        let (nx, ny) = (x as f64 * scale, y as f64 * scale);
+        let sample = |noise: &N| match effect.period {
+            Some(_) => noise.get([nx, ny, t1, t2]),
+            None => noise.get([nx, ny, t1]),
+        };
+        (sample(noise_x), sample(noise_y))
+    })
+}
+
+/// Move each pixel by the amplitude times the offsets from -1 to 1 returned for its position
+fn displace<T: RgbaImage, F>(img: &T, opts: &DisplaceOptions, offsets: F) -> RgbaBuf
+    where F: Fn(u32, u32) -> (f64, f64)
+{
+    let (width, height) = (img.width(), img.height());
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let (dx, dy) = offsets(x, y);
+        let source_x = (x as f64 + dx.clamp(-1.0, 1.0) * opts.amplitude_x).round();
+        let source_y = (y as f64 + dy.clamp(-1.0, 1.0) * opts.amplitude_y).round();
+        img.get_pixel(source_x.clamp(0.0, (width - 1) as f64) as u32, source_y.clamp(0.0, (height - 1) as f64) as u32)
+    })
+}
@@ -0,0 +1,226 @@
+use image::{self, ColorType, ImageFormat, Pixel};
+use image::codecs::jpeg::JpegEncoder;
+use rand::Rng;
+use std::str::FromStr;
+use effects::{Effect, Params, parse_param, unknown_param};
+use util::{GlitchRng, RgbaBuf};
+
+/// How bytes of the compressed data are corrupted
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CorruptionMode {
+    /// Flip a single bit
+    Flip,
+    /// Swap with another byte of the compressed data
+    Shuffle,
+}
+
+impl FromStr for CorruptionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CorruptionMode, String> {
+        match s {
+            "flip" => Ok(CorruptionMode::Flip),
+            "shuffle" => Ok(CorruptionMode::Shuffle),
+            _ => Err(format!("Unknown corruption mode '{}', expected flip or shuffle", s)),
+        }
+    }
+}
+
+/// Options for corrupting the image as JPEG
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct JpegOptions {
+    /// Encoder quality from 1 to 100
+    pub quality: u8,
+    /// Chance of each byte of the compressed data to be corrupted
+    pub rate: f64,
+    pub mode: CorruptionMode,
+    /// How often to corrupt the data again if the decoder rejects it
+    pub retries: u32,
+}
+
+impl Default for JpegOptions {
+    fn default() -> JpegOptions {
+        JpegOptions {
+            quality: 50,
+            rate: 0.0002,
+            mode: CorruptionMode::Flip,
+            retries: 5,
+        }
+    }
+}
+
+impl Params for JpegOptions {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "quality" => self.quality = parse_param(key, value)?,
+            "rate" => self.rate = parse_param(key, value)?,
+            "mode" => self.mode = parse_param(key, value)?,
+            "retries" => self.retries = parse_param(key, value)?,
+            _ => return unknown_param(key),
+        }
+        Ok(())
+    }
+}
+
+impl Effect for JpegOptions {
+    fn apply(&self, img: &RgbaBuf, rng: &mut GlitchRng) -> RgbaBuf {
+        jpeg(img, self, rng)
+    }
+
+    fn step(&self, _: &mut GlitchRng) -> Box<dyn Effect> {
+        Box::new(*self)
+    }
+}
+
+fn jpeg<R: Rng>(img: &RgbaBuf, config: &JpegOptions, rng: &mut R) -> RgbaBuf {
+    println!("* Corrupting JPEG data");
+
+    let mut data = vec![];
+    let quality = u8::min(100, u8::max(1, config.quality));
+    let encoded = JpegEncoder::new_with_quality(&mut data, quality)
+        .encode(img, img.width(), img.height(), ColorType::Rgba8);
+    if let Err(e) = encoded {
+        println!("  Unable to encode JPEG, skipping: {}", e);
+        return img.clone();
+    }
+
+    for _ in 0..=config.retries {
+        let mut corrupted = data.clone();
+        corrupt(&mut corrupted, config, rng);
+        if let Some(decoded) = decode(&corrupted, img) {
+            return decoded;
+        }
+    }
+
+    // Keep at least the compression artifacts
+    println!("  Corrupted data could not be decoded, using the uncorrupted JPEG");
+    decode(&data, img).unwrap_or_else(|| img.clone())
+}
+
+/// Decode a JPEG, restoring the alpha channel JPEG doesn't store. Images of the wrong size are rejected.
+fn decode(data: &[u8], original: &RgbaBuf) -> Option<RgbaBuf> {
+    let decoded = image::load_from_memory_with_format(data, ImageFormat::Jpeg).ok()?.to_rgba8();
+    if decoded.dimensions() != original.dimensions() {
+        return None;
+    }
+
+    let mut decoded = decoded;
+    for (px, original) in decoded.pixels_mut().zip(original.pixels()) {
+        px.channels_mut()[3] = original.channels()[3];
+    }
+    Some(decoded)
+}
+
+/// Corrupt the entropy-coded image data between the start of scan header and the end of image
+/// marker, leaving all headers intact
+fn corrupt<R: Rng>(data: &mut [u8], config: &JpegOptions, rng: &mut R) {
+    let start = match scan_start(data) {
+        Some(start) => start,
+        None => return,
+    };
+    // Keep the end of image marker
+    let end = data.len().saturating_sub(2);
+    if start >= end {
+        return;
+    }
+
+    // 0xFF introduces markers, so it is never written, and neither it nor the byte following it is touched
+    let corruptible = |data: &[u8], i: usize| data[i] != 0xFF && data[i - 1] != 0xFF;
+    for i in start..end {
+        if rng.next_f64() >= config.rate || !corruptible(data, i) {
+            continue;
+        }
+
+        match config.mode {
+            CorruptionMode::Flip => {
+                let flipped = data[i] ^ (1 << rng.gen_range(0, 8));
+                if flipped != 0xFF {
+                    data[i] = flipped;
+                }
+            },
+            CorruptionMode::Shuffle => {
+                let other = rng.gen_range(start, end);
+                if corruptible(data, other) {
+                    data.swap(i, other);
+                }
+            },
+        }
+    }
+}
+
+/// Offset of the first byte after the first start of scan header. The marker segments before it
+/// are skipped by their lengths, as their payloads may contain the marker bytes.
+fn scan_start(data: &[u8]) -> Option<usize> {
+    if data.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+
+    let mut pos = 2;
+    loop {
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        match marker {
+            // Fill byte before a marker
+            0xFF => pos += 1,
+            // Markers without a segment
+            0x01 | 0xD0..=0xD7 => pos += 2,
+            _ => {
+                let length = *data.get(pos + 2)? as usize * 256 + *data.get(pos + 3)? as usize;
+                if marker == 0xDA {
+                    return Some(pos + 2 + length);
+                }
+                pos += 2 + length;
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgba};
+
+    #[test]
+    fn skips_marker_bytes_inside_segments() {
+        let data = [
+            0xFF, 0xD8,
+            // Comment whose payload looks like a start of scan
+            0xFF, 0xFE, 0x00, 0x06, 0xFF, 0xDA, 0x00, 0x02,
+            // Fill bytes, then a restart marker without a segment
+            0xFF, 0xFF, 0xFF, 0xD0,
+            0xFF, 0xDA, 0x00, 0x04, 0x01, 0x02,
+            0x12, 0x34,
+        ];
+        assert_eq!(scan_start(&data), Some(20));
+    }
+
+    #[test]
+    fn rejects_data_that_is_not_jpeg() {
+        assert_eq!(scan_start(&[]), None);
+        assert_eq!(scan_start(&[0x89, b'P', b'N', b'G']), None);
+        // Payload bytes where a marker should be
+        assert_eq!(scan_start(&[0xFF, 0xD8, 0xFF, 0xFE, 0x00, 0x02, 0x12, 0xFF, 0xDA, 0x00, 0x02]), None);
+        // Ends before the start of scan
+        assert_eq!(scan_start(&[0xFF, 0xD8, 0xFF, 0xFE, 0x00, 0x04, 0xFF]), None);
+        assert_eq!(scan_start(&[0xFF, 0xD8, 0xFF, 0xDA, 0x00]), None);
+    }
+
+    #[test]
+    fn finds_scan_of_encoded_image() {
+        let img: RgbaBuf = ImageBuffer::from_pixel(16, 16, Rgba([200, 100, 50, 255]));
+        let mut data = vec![];
+        JpegEncoder::new_with_quality(&mut data, 80)
+            .encode(&img, img.width(), img.height(), ColorType::Rgba8)
+            .unwrap();
+
+        let start = scan_start(&data).unwrap();
+        let header = (2..start).rev().find(|&pos| data[pos - 2..pos] == [0xFF, 0xDA]).unwrap();
+        assert_eq!(start, header + data[header] as usize * 256 + data[header + 1] as usize);
+        assert!(start < data.len() - 2);
+    }
+}
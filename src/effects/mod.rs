@@ -0,0 +1,260 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use options::Options;
+use util::{GlitchRng, RgbaBuf};
+
+mod bits;
+mod blocks;
+mod composite;
+mod crt;
+mod datamosh;
+mod displace;
+mod jpeg;
+mod noise;
+mod scanlines;
+mod shift;
+mod sort;
+mod tiles;
+mod vhs;
+mod wind;
+
+pub use self::bits::{BitPlaneOptions, CrushOptions};
+pub use self::blocks::{BlockShift, BlockShiftOptions};
+pub use self::composite::{Composite, CompositeOptions};
+pub use self::crt::CrtOptions;
+pub use self::datamosh::{Datamosh, DatamoshOptions};
+pub use self::displace::{Displace, DisplaceOptions};
+pub use self::jpeg::JpegOptions;
+pub use self::noise::{Grain, NoiseOptions};
+pub use self::scanlines::{Scanlines, ScanlineOptions};
+pub use self::shift::{ChannelShift, LimitedShiftOptions, ShiftOptions};
+pub use self::sort::SortOptions;
+pub use self::tiles::{TileOptions, TileScramble};
+pub use self::vhs::{Vhs, VhsOptions};
+pub use self::wind::{WindDirection, WindOptions};
+
+/// A single stage of the glitch pipeline. Its parameters can be changed between frames.
+pub trait Effect: Params {
+    /// Apply the effect to an image
+    fn apply(&self, img: &RgbaBuf, rng: &mut GlitchRng) -> RgbaBuf;
+
+    /// Advance the effect to the next frame of an animation
+    fn step(&self, rng: &mut GlitchRng) -> Box<dyn Effect>;
+}
+
+/// Effect options that can be adjusted by name, e.g. from a pipeline stage
+pub trait Params {
+    /// Set the parameter `key` from its textual representation
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String>;
+}
+
+/// Properties of the render an effect is constructed for
+#[derive(Copy, Clone)]
+pub struct RenderInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Number of frames after which the animation should seamlessly repeat, if looping
+    pub period: Option<usize>,
+}
+
+/// A selection of color channels, written as a combination of the letters `r`, `g` and `b`
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Channels {
+    pub red: bool,
+    pub green: bool,
+    pub blue: bool,
+}
+
+impl Channels {
+    pub fn all() -> Channels {
+        Channels { red: true, green: true, blue: true }
+    }
+
+    /// Whether the channel with the given index (0 for red, 1 for green, 2 for blue) is selected
+    pub fn contains(&self, channel: usize) -> bool {
+        match channel {
+            0 => self.red,
+            1 => self.green,
+            2 => self.blue,
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Channels {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Channels, String> {
+        let mut channels = Channels { red: false, green: false, blue: false };
+        for c in s.chars() {
+            match c {
+                'r' => channels.red = true,
+                'g' => channels.green = true,
+                'b' => channels.blue = true,
+                _ => return Err(format!("Unknown channel '{}', expected a combination of r, g and b", c)),
+            }
+        }
+        Ok(channels)
+    }
+}
+
+impl fmt::Display for Channels {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (selected, name) in [(self.red, "r"), (self.green, "g"), (self.blue, "b")].iter() {
+            if *selected {
+                write!(f, "{}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<Channels> for String {
+    fn from(channels: Channels) -> String {
+        channels.to_string()
+    }
+}
+
+impl TryFrom<String> for Channels {
+    type Error = String;
+
+    fn try_from(spec: String) -> Result<Channels, String> {
+        spec.parse()
+    }
+}
+
+/// A constructed effect, or why it could not be constructed
+pub type BuildResult = Result<Box<dyn Effect>, String>;
+
+/// Registry entry describing how to construct an effect
+pub struct EffectInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Construct the effect from the render options
+    pub build: fn(&Options, &RenderInfo, &mut GlitchRng) -> BuildResult,
+    /// The part of the render options configuring this effect
+    pub params: fn(&mut Options) -> &mut dyn Params,
+}
+
+/// All known effects, selectable by name
+pub static EFFECTS: &[EffectInfo] = &[
+    EffectInfo {
+        name: "scanlines",
+        description: "Scanline gaps and sinuoid desync of lines",
+        build: |opts, info, rng| Ok(Box::new(Scanlines::random(&opts.scan, info, rng))),
+        params: |opts| &mut opts.scan,
+    },
+    EffectInfo {
+        name: "color-shift",
+        description: "Offset the red channel against the green/blue channels",
+        build: |opts, _, _| Ok(Box::new(opts.shift)),
+        params: |opts| &mut opts.shift,
+    },
+    EffectInfo {
+        name: "rgb-shift",
+        description: "Offset each color channel independently",
+        build: |opts, info, rng| Ok(Box::new(ChannelShift::random(&opts.rgb_shift, info, rng)?)),
+        params: |opts| &mut opts.rgb_shift,
+    },
+    EffectInfo {
+        name: "wind",
+        description: "Stretch single pixels into streaks in any direction",
+        build: |opts, _, _| Ok(Box::new(opts.wind)),
+        params: |opts| &mut opts.wind,
+    },
+    EffectInfo {
+        name: "blocks",
+        description: "Shift blocks of lines or rectangles sideways, vertically or in a single color channel",
+        build: |opts, info, rng| Ok(Box::new(BlockShift::random(&opts.blocks, info, rng))),
+        params: |opts| &mut opts.blocks,
+    },
+    EffectInfo {
+        name: "grain",
+        description: "Add film grain",
+        build: |opts, info, rng| Ok(Box::new(Grain::random(&opts.grain, info, rng))),
+        params: |opts| &mut opts.grain,
+    },
+    EffectInfo {
+        name: "sort",
+        description: "Sort runs of pixels along rows or columns",
+        build: |opts, _, _| Ok(Box::new(opts.sort)),
+        params: |opts| &mut opts.sort,
+    },
+    EffectInfo {
+        name: "jpeg",
+        description: "Corrupt the compressed data of the image encoded as JPEG",
+        build: |opts, _, _| Ok(Box::new(opts.jpeg)),
+        params: |opts| &mut opts.jpeg,
+    },
+    EffectInfo {
+        name: "datamosh",
+        description: "Drag the pixels of a held frame along the motion between frames",
+        build: |opts, _, _| Ok(Box::new(Datamosh::new(&opts.datamosh))),
+        params: |opts| &mut opts.datamosh,
+    },
+    EffectInfo {
+        name: "crush",
+        description: "Reduce the bit depth of color channels",
+        build: |opts, _, _| Ok(Box::new(opts.crush)),
+        params: |opts| &mut opts.crush,
+    },
+    EffectInfo {
+        name: "bitplanes",
+        description: "Corrupt bit-planes of color channels in random horizontal bands",
+        build: |opts, _, _| Ok(Box::new(opts.bitplanes)),
+        params: |opts| &mut opts.bitplanes,
+    },
+    EffectInfo {
+        name: "vhs",
+        description: "Rolling tracking bar, head switching and chroma bleed of a VHS tape",
+        build: |opts, info, rng| Ok(Box::new(Vhs::random(&opts.vhs, info, rng))),
+        params: |opts| &mut opts.vhs,
+    },
+    EffectInfo {
+        name: "composite",
+        description: "Band-limited chroma, dot crawl and crosstalk of NTSC or PAL composite video",
+        build: |opts, info, rng| Ok(Box::new(Composite::random(&opts.composite, info, rng))),
+        params: |opts| &mut opts.composite,
+    },
+    EffectInfo {
+        name: "crt",
+        description: "Screen curvature, phosphor mask, bloom and vignette of a CRT display",
+        build: |opts, _, _| Ok(Box::new(opts.crt)),
+        params: |opts| &mut opts.crt,
+    },
+    EffectInfo {
+        name: "tiles",
+        description: "Permute, duplicate, mirror or rotate tiles of a grid or strips",
+        build: |opts, info, rng| Ok(Box::new(TileScramble::random(&opts.tiles, info, rng))),
+        params: |opts| &mut opts.tiles,
+    },
+    EffectInfo {
+        name: "displace",
+        description: "Offset pixels by a noise field or a grayscale displacement map",
+        build: |opts, info, rng| Ok(Box::new(Displace::random(&opts.displace, info, rng)?)),
+        params: |opts| &mut opts.displace,
+    },
+];
+
+/// Effects applied when no pipeline is given
+pub static DEFAULT_PIPELINE: &str = "scanlines,color-shift,wind,blocks";
+
+/// Look up an effect by name
+pub fn lookup(name: &str) -> Option<&'static EffectInfo> {
+    EFFECTS.iter().find(|effect| effect.name == name)
+}
+
+/// Parse a parameter value, naming the parameter on failure.
+/// Fractional values are rounded for integer parameters, e.g. when interpolating between keyframes.
+pub fn parse_param<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value.parse::<T>()
+        .ok()
+        .or_else(|| value.parse::<f64>().ok().and_then(|v| (v.round() + 0.0).to_string().parse::<T>().ok()))
+        .ok_or_else(|| format!("Invalid value '{}' for parameter '{}'", value, key))
+}
+
+fn unknown_param(key: &str) -> Result<(), String> {
+    Err(format!("Unknown parameter '{}'", key))
+}
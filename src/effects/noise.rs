@@ -0,0 +1,264 @@
+use image::{ImageBuffer, Rgba, Pixel};
+use noise::{self, MultiFractal, NoiseModule, Seedable};
+use rand::Rng;
+use std::f64::consts::PI;
+use std::str::FromStr;
+use effects::{Effect, Params, RenderInfo, parse_param, unknown_param};
+use util::{GlitchRng, RgbaBuf, RgbaImage, clamping_add};
+
+/// Whether all color channels share the same grain
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GrainColor {
+    Mono,
+    Rgb,
+}
+
+impl FromStr for GrainColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<GrainColor, String> {
+        match s {
+            "mono" => Ok(GrainColor::Mono),
+            "rgb" => Ok(GrainColor::Rgb),
+            _ => Err(format!("Unknown grain color '{}', expected mono or rgb", s)),
+        }
+    }
+}
+
+/// How the grain is combined with the image
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GrainBlend {
+    /// Brighten the image by the grain
+    Add,
+    /// Darken dark and brighten light areas, keeping the overall brightness
+    Overlay,
+}
+
+impl FromStr for GrainBlend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<GrainBlend, String> {
+        match s {
+            "add" => Ok(GrainBlend::Add),
+            "overlay" => Ok(GrainBlend::Overlay),
+            _ => Err(format!("Unknown grain blend mode '{}', expected add or overlay", s)),
+        }
+    }
+}
+
+/// How the grain changes between frames of an animation
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GrainMotion {
+    /// Fresh grain every frame
+    Reseed,
+    /// The grain flows smoothly, moving `speed` through the noise each frame
+    Coherent,
+}
+
+impl FromStr for GrainMotion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<GrainMotion, String> {
+        match s {
+            "reseed" => Ok(GrainMotion::Reseed),
+            "coherent" => Ok(GrainMotion::Coherent),
+            _ => Err(format!("Unknown grain motion '{}', expected reseed or coherent", s)),
+        }
+    }
+}
+
+/// Options for the film grain effect
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct NoiseOptions {
+    pub frequency: f64,
+    pub lacunarity: f64,
+    pub octaves: usize,
+    pub persistence: f64,
+    pub alpha: f64,
+    pub color: GrainColor,
+    pub blend: GrainBlend,
+    pub motion: GrainMotion,
+    pub speed: f64,
+}
+
+impl Default for NoiseOptions {
+    fn default() -> NoiseOptions {
+        NoiseOptions {
+            frequency: 96.0,
+            lacunarity: 2.0,
+            octaves: 6,
+            persistence: 0.5,
+            alpha: 0.2,
+            color: GrainColor::Mono,
+            blend: GrainBlend::Add,
+            motion: GrainMotion::Reseed,
+            speed: 0.05,
+        }
+    }
+}
+
+impl Params for NoiseOptions {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "frequency" => self.frequency = parse_param(key, value)?,
+            "lacunarity" => self.lacunarity = parse_param(key, value)?,
+            "octaves" => self.octaves = parse_param(key, value)?,
+            "persistence" => self.persistence = parse_param(key, value)?,
+            "alpha" => self.alpha = parse_param(key, value)?,
+            "color" => self.color = parse_param(key, value)?,
+            "blend" => self.blend = parse_param(key, value)?,
+            "motion" => self.motion = parse_param(key, value)?,
+            "speed" => self.speed = parse_param(key, value)?,
+            _ => return unknown_param(key),
+        }
+        Ok(())
+    }
+}
+
+/// The film grain effect
+#[derive(Copy, Clone)]
+pub struct Grain {
+    options: NoiseOptions,
+    seed: u32,
+    frame: usize,
+    period: Option<usize>,
+}
+
+impl Grain {
+    pub fn random<R: Rng>(options: &NoiseOptions, info: &RenderInfo, rng: &mut R) -> Grain {
+        Grain {
+            options: *options,
+            seed: rng.next_u32(),
+            frame: 0,
+            period: info.period,
+        }
+    }
+
+    /// Seed of the noise and position along the time axis of the noise for the current frame
+    fn time(&self) -> (u32, [f64; 2]) {
+        let frame = self.period.map_or(self.frame, |period| self.frame % period);
+        match self.options.motion {
+            // Repeating the seeds of a period keeps looping animations seamless
+            GrainMotion::Reseed => (self.seed.wrapping_add(frame as u32 * 3), [0.0, 0.0]),
+            GrainMotion::Coherent => match self.period {
+                // Travel on a circle, so the last frame leads back to the first
+                Some(period) => {
+                    let radius = self.options.speed * period as f64 / (2.0 * PI);
+                    let angle = 2.0 * PI * frame as f64 / period as f64;
+                    (self.seed, [radius * f64::cos(angle), radius * f64::sin(angle)])
+                },
+                None => (self.seed, [self.options.speed * frame as f64, 0.0]),
+            },
+        }
+    }
+}
+
+impl Params for Grain {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        self.options.set_param(key, value)
+    }
+}
+
+impl Effect for Grain {
+    fn apply(&self, img: &RgbaBuf, _: &mut GlitchRng) -> RgbaBuf {
+        noise(img, self)
+    }
+
+    fn step(&self, _: &mut GlitchRng) -> Box<dyn Effect> {
+        Box::new(Grain {
+            frame: self.frame + 1,
+            ..*self
+        })
+    }
+}
+
+fn noise<T: RgbaImage>(img: &T, grain: &Grain) -> RgbaBuf {
+    println!("* Adding grain");
+
+    let config = &grain.options;
+    let (seed, [t1, t2]) = grain.time();
+    let channels = match config.color {
+        GrainColor::Mono => 1,
+        GrainColor::Rgb => 3,
+    };
+    let noises = (0..channels)
+        .map(|channel| noise::Fbm::new()
+            .set_frequency(config.frequency)
+            .set_lacunarity(config.lacunarity)
+            .set_octaves(config.octaves)
+            .set_persistence(config.persistence)
+            .set_seed(seed.wrapping_add(channel) as usize))
+        .collect::<Vec<_>>();
+
+    ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        // Normalize coordinates to [0,1]
+        let (nx, ny) = (x as f64 / img.width() as f64, y as f64 / img.height() as f64);
+        let vals = noises.iter().map(|noise| match config.motion {
+            GrainMotion::Reseed => noise.get([nx, ny]),
+            GrainMotion::Coherent => noise.get([nx, ny, t1, t2]),
+        }).collect::<Vec<_>>();
+        let val = |channel: usize| vals[channel % vals.len()];
+
+        let (r, g, b, a) = img.get_pixel(x, y).channels4();
+        let noise_alpha = config.alpha;
+
+        let (r, g, b, a) = match config.blend {
+            GrainBlend::Add => {
+                let (vr, vg, vb) = (additive(val(0), noise_alpha), additive(val(1), noise_alpha), additive(val(2), noise_alpha));
+                if a == 0 {
+                    (vr, vg, vb, vr.max(vg).max(vb))
+                } else {
+                    let af = a as f64 / u8::MAX as f64;
+                    /*
+                    let r = blend_alpha_one_minus_alpha(noise_alpha, val, r);
+                    let g = blend_alpha_one_minus_alpha(noise_alpha, val, g);
+                    let b = blend_alpha_one_minus_alpha(noise_alpha, val, b);
+                    */
+                    let r = clamping_add(r as u32, vr as i32, u8::MAX as u32) as u8;
+                    let g = clamping_add(g as u32, vg as i32, u8::MAX as u32) as u8;
+                    let b = clamping_add(b as u32, vb as i32, u8::MAX as u32) as u8;
+                    let a = ((af + (1.0 - af) * noise_alpha) * u8::MAX as f64) as u8;
+                    (r, g, b, a)
+                }
+            },
+            GrainBlend::Overlay => (
+                overlay(r, val(0), noise_alpha),
+                overlay(g, val(1), noise_alpha),
+                overlay(b, val(2), noise_alpha),
+                a,
+            ),
+        };
+
+        Rgba::from_channels(r, g, b, a)
+    })
+}
+
+/// Grain brightness added to a channel
+fn additive(val: f64, alpha: f64) -> u8 {
+    // Apply sigmoid shaping
+    let val = val * 4.0; // Sharper edge
+    let val = val - 1.25; // Stay in black area longer
+    let exp = f64::exp(val);
+    let val = exp / (1.0 + exp);
+
+    (val * alpha * (u8::MAX as f64)) as u8
+}
+
+/// Overlay blend of a channel with grain centered around mid-gray
+fn overlay(base: u8, val: f64, alpha: f64) -> u8 {
+    let base = base as f64 / u8::MAX as f64;
+    let layer = (0.5 + val * 0.5).clamp(0.0, 1.0);
+    let blended = if base < 0.5 {
+        2.0 * base * layer
+    } else {
+        1.0 - 2.0 * (1.0 - base) * (1.0 - layer)
+    };
+    let result = base + (blended - base) * alpha;
+
+    (result.clamp(0.0, 1.0) * u8::MAX as f64) as u8
+}
@@ -0,0 +1,136 @@
+use image::{ImageBuffer, Rgba, Pixel};
+use rand::Rng;
+use std::f64::consts::PI;
+use effects::{Effect, Params, RenderInfo, parse_param, unknown_param};
+use util::{GlitchRng, RgbaBuf, RgbaImage, blend, clamping_add};
+
+/// Options for the scanline and sinuoid desync effect
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct ScanlineOptions {
+    #[serde(rename = "height")]
+    pub vert_dist: u32,
+    #[serde(rename = "gap")]
+    pub gap_size: u32,
+    #[serde(rename = "desync-amp")]
+    pub desync_amplitude: f64,
+    #[serde(rename = "desync-freq")]
+    pub desync_frequency: f64,
+}
+
+impl ScanlineOptions {
+    pub fn new(vert_dist: u32, gap_size: u32, desync_amplitude: f64, desync_frequency: f64) -> ScanlineOptions {
+        ScanlineOptions {
+            vert_dist,
+            gap_size,
+            desync_amplitude,
+            desync_frequency,
+        }
+    }
+}
+
+impl Default for ScanlineOptions {
+    fn default() -> ScanlineOptions {
+        ScanlineOptions::new(6, 3, 6.0, 0.3)
+    }
+}
+
+impl Params for ScanlineOptions {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "height" => self.vert_dist = parse_param(key, value)?,
+            "gap" => self.gap_size = parse_param(key, value)?,
+            "desync-amp" => self.desync_amplitude = parse_param(key, value)?,
+            "desync-freq" => self.desync_frequency = parse_param(key, value)?,
+            _ => return unknown_param(key),
+        }
+        Ok(())
+    }
+}
+
+/// The scanline and sinuoid desync effect
+#[derive(Copy, Clone)]
+pub struct Scanlines {
+    options: ScanlineOptions,
+    desync_phase_shift: f64,
+    period: Option<usize>,
+}
+
+impl Scanlines {
+    pub fn random<R: Rng>(options: &ScanlineOptions, info: &RenderInfo, rng: &mut R) -> Scanlines {
+        let desync_phase_shift = rng.next_f64() * options.desync_frequency;
+        Scanlines {
+            options: *options,
+            desync_phase_shift,
+            period: info.period,
+        }
+    }
+}
+
+impl Params for Scanlines {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        self.options.set_param(key, value)
+    }
+}
+
+/// Phase shift of the desync wave per frame, relative to its frequency
+const DESYNC_SPEED: f64 = 0.1;
+
+impl Effect for Scanlines {
+    fn apply(&self, img: &RgbaBuf, rng: &mut GlitchRng) -> RgbaBuf {
+        scanlines(img, self, rng)
+    }
+
+    fn step(&self, _: &mut GlitchRng) -> Box<dyn Effect> {
+        let cycle = 2.0 * PI * self.options.desync_frequency;
+        let phase_step = match self.period {
+            // The desync wave repeats after a phase shift of 2π times its frequency. Complete the
+            // whole number of cycles per period closest to the speed of unlooped animations.
+            Some(period) => cycle * (DESYNC_SPEED * period as f64 / (2.0 * PI)).round() / period as f64,
+            None => DESYNC_SPEED * self.options.desync_frequency,
+        };
+        let desync_phase_shift = self.desync_phase_shift + phase_step;
+        Box::new(Scanlines {
+            desync_phase_shift,
+            ..*self
+        })
+    }
+}
+
+fn scanlines<T: RgbaImage, R: Rng>(img: &T, config: &Scanlines, rng: &mut R) -> RgbaBuf {
+    println!("* Adding scanlines");
+
+    let opts = &config.options;
+    ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        // Insert scanline gap
+        if y % (opts.vert_dist + opts.gap_size) >= opts.vert_dist {
+            let base_y = y - (y % (opts.vert_dist + opts.gap_size));
+            let prev_line = base_y + opts.vert_dist - 1;
+            let next_line = prev_line + opts.gap_size;
+
+            let mut blend_sources = vec![];
+            for &(index, weight) in [(-1, 8), (0, 4), (1, 8)].iter() {
+                let src_x = clamping_add(x, index, img.width() - 1);
+                let (r, g, b, a) = img.get_pixel(src_x, prev_line).channels4();
+                blend_sources.push(Rgba::from_channels(r, g, b, a / weight));
+
+                if next_line < img.height() {
+                    let (r, g, b, a) = img.get_pixel(src_x, next_line).channels4();
+                    blend_sources.push(Rgba::from_channels(r, g, b, a / weight));
+                }
+            }
+
+            return blend(&blend_sources[..]);
+        }
+
+        // Desync lines
+        let line = y / (opts.vert_dist + opts.gap_size);
+        let line = line as f64 + config.desync_phase_shift;
+
+        let desync_x_shift = f64::sin(line / opts.desync_frequency) * opts.desync_amplitude;
+        let desync_x_shift = desync_x_shift + 0.3 * opts.desync_amplitude * (rng.next_f64() - 0.5);
+        let x = clamping_add(x, desync_x_shift as i32, img.width() - 1);
+        img.get_pixel(x, y)
+    })
+}
@@ -0,0 +1,430 @@
+use image::{ImageBuffer, Rgba, Pixel};
+use rand::Rng;
+use rand::distributions::{Normal, Range, IndependentSample};
+use std::f64::consts::PI;
+use effects::{Effect, Params, RenderInfo, parse_param, unknown_param};
+use util::{GlitchRng, RgbaBuf, RgbaImage, blend, clamping_add};
+
+/// Options for the red/cyan color offsetting
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct LimitedShiftOptions {
+    pub distance: i32
+}
+
+impl LimitedShiftOptions {
+    pub fn new(distance: i32) -> LimitedShiftOptions {
+        LimitedShiftOptions {
+            distance
+        }
+    }
+}
+
+impl Default for LimitedShiftOptions {
+    fn default() -> LimitedShiftOptions {
+        LimitedShiftOptions::new(4)
+    }
+}
+
+impl Params for LimitedShiftOptions {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "distance" => self.distance = parse_param(key, value)?,
+            _ => return unknown_param(key),
+        }
+        Ok(())
+    }
+}
+
+impl Effect for LimitedShiftOptions {
+    fn apply(&self, img: &RgbaBuf, _: &mut GlitchRng) -> RgbaBuf {
+        offset_red_cyan(img, self)
+    }
+
+    fn step(&self, _: &mut GlitchRng) -> Box<dyn Effect> {
+        Box::new(*self)
+    }
+}
+
+/// Options for color channel offsetting. Each channel can override the shared values.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct ShiftOptions {
+    pub offset: f64,
+    pub radius: f64,
+    pub max_move: f64,
+    #[serde(skip_serializing_if = "ChannelOverrides::is_empty")]
+    pub red: ChannelOverrides,
+    #[serde(skip_serializing_if = "ChannelOverrides::is_empty")]
+    pub green: ChannelOverrides,
+    #[serde(skip_serializing_if = "ChannelOverrides::is_empty")]
+    pub blue: ChannelOverrides,
+}
+
+/// Per-channel values taking precedence over the shared ones of `ShiftOptions`
+#[derive(Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct ChannelOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub radius: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_move: Option<f64>,
+}
+
+impl ChannelOverrides {
+    fn is_empty(&self) -> bool {
+        self.offset.is_none() && self.radius.is_none() && self.max_move.is_none()
+    }
+}
+
+impl ShiftOptions {
+    pub fn new(offset: f64, radius: f64, max_move: f64) -> ShiftOptions {
+        ShiftOptions {
+            offset,
+            radius,
+            max_move,
+            red: ChannelOverrides::default(),
+            green: ChannelOverrides::default(),
+            blue: ChannelOverrides::default(),
+        }
+    }
+
+    /// Offset, radius and max move of a single channel
+    fn channel(&self, overrides: &ChannelOverrides) -> (f64, f64, f64) {
+        (
+            overrides.offset.unwrap_or(self.offset),
+            overrides.radius.unwrap_or(self.radius),
+            overrides.max_move.unwrap_or(self.max_move),
+        )
+    }
+}
+
+impl Default for ShiftOptions {
+    fn default() -> ShiftOptions {
+        ShiftOptions::new(4.0, 2.0, 1.0)
+    }
+}
+
+/// Split a parameter like `red-offset` into the channel index and the shared parameter name
+fn channel_param(key: &str) -> (Option<usize>, &str) {
+    let channels = [("red-", 0), ("green-", 1), ("blue-", 2)];
+    for &(prefix, channel) in channels.iter() {
+        if let Some(param) = key.strip_prefix(prefix) {
+            return (Some(channel), param);
+        }
+    }
+    (None, key)
+}
+
+/// Reject negative distances, which the drift of the channels can't be sampled with
+fn check_distance(key: &str, value: f64) -> Result<f64, String> {
+    if value < 0.0 {
+        return Err(format!("Invalid value '{}' for parameter '{}', expected a non-negative number", value, key));
+    }
+    Ok(value)
+}
+
+impl Params for ShiftOptions {
+    /// Setting a shared value also removes the overrides of that value
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let (channel, param) = channel_param(key);
+        let value = match param {
+            "offset" => parse_param::<f64>(key, value)?,
+            "radius" | "max-move" => check_distance(key, parse_param(key, value)?)?,
+            _ => return unknown_param(key),
+        };
+
+        let mut channels = [&mut self.red, &mut self.green, &mut self.blue];
+        match channel {
+            Some(channel) => {
+                let overrides = &mut channels[channel];
+                match param {
+                    "offset" => overrides.offset = Some(value),
+                    "radius" => overrides.radius = Some(value),
+                    _ => overrides.max_move = Some(value),
+                }
+            },
+            None => {
+                for overrides in channels.iter_mut() {
+                    match param {
+                        "offset" => overrides.offset = None,
+                        "radius" => overrides.radius = None,
+                        _ => overrides.max_move = None,
+                    }
+                }
+                match param {
+                    "offset" => self.offset = value,
+                    "radius" => self.radius = value,
+                    _ => self.max_move = value,
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+/// The color channel offsetting effect
+#[derive(Copy, Clone)]
+pub struct ChannelShift {
+    r: ChannelShiftOptions,
+    g: ChannelShiftOptions,
+    b: ChannelShiftOptions,
+}
+
+#[derive(Copy, Clone)]
+struct ChannelShiftOptions {
+    angle: f64,
+    base_shift_x: f64,
+    base_shift_y: f64,
+    current_shift_x: f64,
+    current_shift_y: f64,
+    radius: f64,
+    max_move: f64,
+    orbit: Option<Orbit>,
+}
+
+const ORBIT_HARMONICS: usize = 3;
+
+/// A closed random path around the base shift, used for seamlessly looping animations
+#[derive(Copy, Clone)]
+struct Orbit {
+    period: usize,
+    frame: usize,
+    amplitudes: [(f64, f64); ORBIT_HARMONICS],
+    phases: [(f64, f64); ORBIT_HARMONICS],
+}
+
+impl ChannelShift {
+    pub fn random<R: Rng>(options: &ShiftOptions, info: &RenderInfo, rng: &mut R) -> Result<ChannelShift, String> {
+        // Options read from recipes haven't been checked by `set_param`
+        check_distance("radius", options.radius)?;
+        check_distance("max-move", options.max_move)?;
+        for &(name, ref overrides) in [("red", options.red), ("green", options.green), ("blue", options.blue)].iter() {
+            if let Some(radius) = overrides.radius {
+                check_distance(&format!("{}-radius", name), radius)?;
+            }
+            if let Some(max_move) = overrides.max_move {
+                check_distance(&format!("{}-max-move", name), max_move)?;
+            }
+        }
+
+        let (r_offset, r_radius, r_max_move) = options.channel(&options.red);
+        let (g_offset, g_radius, g_max_move) = options.channel(&options.green);
+        let (b_offset, b_radius, b_max_move) = options.channel(&options.blue);
+        Ok(ChannelShift {
+            r: ChannelShiftOptions::random(r_offset, r_radius, r_max_move, info.period, rng),
+            g: ChannelShiftOptions::random(g_offset, g_radius, g_max_move, info.period, rng),
+            b: ChannelShiftOptions::random(b_offset, b_radius, b_max_move, info.period, rng),
+        })
+    }
+}
+
+impl Params for ChannelShift {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let (only, param) = channel_param(key);
+        let mut channels = [&mut self.r, &mut self.g, &mut self.b];
+        for (i, channel) in channels.iter_mut().enumerate() {
+            if only.is_some() && only != Some(i) {
+                continue;
+            }
+            match param {
+                "offset" => channel.set_offset(parse_param(key, value)?),
+                "radius" => channel.radius = check_distance(key, parse_param(key, value)?)?,
+                "max-move" => channel.max_move = check_distance(key, parse_param(key, value)?)?,
+                _ => return unknown_param(key),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Effect for ChannelShift {
+    fn apply(&self, img: &RgbaBuf, _: &mut GlitchRng) -> RgbaBuf {
+        offset_channels(img, self)
+    }
+
+    fn step(&self, rng: &mut GlitchRng) -> Box<dyn Effect> {
+        Box::new(ChannelShift {
+            r: self.r.step(rng),
+            g: self.g.step(rng),
+            b: self.b.step(rng),
+        })
+    }
+}
+
+impl ChannelShiftOptions {
+    fn random<R: Rng>(offset: f64, radius: f64, max_move: f64, period: Option<usize>, rng: &mut R) -> ChannelShiftOptions {
+        // let distribution = Normal::new(0, offset/3.0);
+
+        let distribution = Range::new(0.0, 2.0 * PI);
+        let angle = distribution.ind_sample(rng);
+        let base_shift_x = f64::cos(angle) * offset;
+        let base_shift_y = f64::sin(angle) * offset;
+
+        let orbit = period.map(|period| Orbit::random(period, rng));
+        let (orbit_x, orbit_y) = orbit.map_or((0.0, 0.0), |orbit| orbit.offset(radius, max_move));
+
+        ChannelShiftOptions {
+            angle,
+            base_shift_x,
+            base_shift_y,
+            current_shift_x: base_shift_x + orbit_x,
+            current_shift_y: base_shift_y + orbit_y,
+            radius,
+            max_move,
+            orbit,
+        }
+    }
+
+    /// Change the distance of the base shift, moving the current shift along with it
+    fn set_offset(&mut self, offset: f64) {
+        let base_shift_x = f64::cos(self.angle) * offset;
+        let base_shift_y = f64::sin(self.angle) * offset;
+        self.current_shift_x += base_shift_x - self.base_shift_x;
+        self.current_shift_y += base_shift_y - self.base_shift_y;
+        self.base_shift_x = base_shift_x;
+        self.base_shift_y = base_shift_y;
+    }
+
+    fn step<R: Rng>(&self, rng: &mut R) -> ChannelShiftOptions {
+        if let Some(orbit) = self.orbit {
+            let orbit = Orbit {
+                frame: (orbit.frame + 1) % orbit.period,
+                ..orbit
+            };
+            let (orbit_x, orbit_y) = orbit.offset(self.radius, self.max_move);
+            return ChannelShiftOptions {
+                current_shift_x: self.base_shift_x + orbit_x,
+                current_shift_y: self.base_shift_y + orbit_y,
+                orbit: Some(orbit),
+                ..*self
+            };
+        }
+
+        // Chosen so ~99.7% of values will lie within radius
+        let distribution = Normal::new(0.0, self.radius / 3.0f64);
+        let new_x = self.base_shift_x + distribution.ind_sample(rng);
+        let new_y = self.base_shift_y + distribution.ind_sample(rng);
+
+        let mut dx = new_x - self.current_shift_x;
+        let mut dy = new_y - self.current_shift_y;
+        let dist_sq = dx * dx + dy * dy;
+        if dist_sq > self.max_move * self.max_move {
+            let scale = self.max_move / f64::sqrt(dist_sq);
+            dx *= scale;
+            dy *= scale;
+        }
+
+        ChannelShiftOptions {
+            current_shift_x: self.current_shift_x + dx,
+            current_shift_y: self.current_shift_y + dy,
+            ..*self
+        }
+    }
+}
+
+impl Orbit {
+    fn random<R: Rng>(period: usize, rng: &mut R) -> Orbit {
+        let mut amplitudes = [(0.0, 0.0); ORBIT_HARMONICS];
+        let mut phases = [(0.0, 0.0); ORBIT_HARMONICS];
+        for k in 0..ORBIT_HARMONICS {
+            // Higher harmonics add wobble, but shouldn't dominate the path
+            let falloff = (k + 1) as f64;
+            amplitudes[k] = (rng.next_f64() / falloff, rng.next_f64() / falloff);
+            phases[k] = (rng.next_f64() * 2.0 * PI, rng.next_f64() * 2.0 * PI);
+        }
+
+        Orbit {
+            period,
+            frame: 0,
+            amplitudes,
+            phases,
+        }
+    }
+
+    /// Offset from the base shift at the current frame, scaled so it stays within `radius`
+    /// and moves at most `max_move` per frame
+    fn offset(&self, radius: f64, max_move: f64) -> (f64, f64) {
+        let t = 2.0 * PI * self.frame as f64 / self.period as f64;
+
+        let (mut x, mut y) = (0.0, 0.0);
+        let (mut extent_x, mut extent_y) = (0.0, 0.0);
+        let (mut speed_x, mut speed_y) = (0.0, 0.0);
+        for k in 0..ORBIT_HARMONICS {
+            let n = (k + 1) as f64;
+            let (amp_x, amp_y) = self.amplitudes[k];
+            let (phase_x, phase_y) = self.phases[k];
+            x += amp_x * f64::cos(n * t + phase_x);
+            y += amp_y * f64::cos(n * t + phase_y);
+            extent_x += amp_x;
+            extent_y += amp_y;
+            speed_x += amp_x * n;
+            speed_y += amp_y * n;
+        }
+
+        let extent = f64::hypot(extent_x, extent_y);
+        let speed = f64::hypot(speed_x, speed_y) * 2.0 * PI / self.period as f64;
+        if extent == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let scale = f64::min(radius / extent, max_move / speed);
+        (x * scale, y * scale)
+    }
+}
+
+fn offset_red_cyan<T: RgbaImage>(img: &T, config: &LimitedShiftOptions) -> RgbaBuf {
+    println!("* Offsetting color channels");
+
+    ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        let red_x = clamping_add(x, config.distance, img.width() - 1);
+        let (r, _, _, a) = img.get_pixel(red_x, y).channels4();
+        let red_px = Rgba::from_channels(r, 0, 0, a);
+
+        let cyan_x = clamping_add(x, -config.distance, img.width() - 1);
+        let (_, g, b, a) = img.get_pixel(cyan_x, y).channels4();
+        let cyan_px = Rgba::from_channels(0, g, b, a);
+
+        blend(&[red_px, cyan_px])
+    })
+}
+
+fn offset_channels<T: RgbaImage>(img: &T, config: &ChannelShift) -> RgbaBuf {
+    println!("* Offsetting color channels");
+
+    let r_channel = ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        let x = clamping_add(x, config.r.current_shift_x as i32, img.width() - 1);
+        let y = clamping_add(y, config.r.current_shift_y as i32, img.height() - 1);
+
+        let (r, _, _, a) = img.get_pixel(x, y).channels4();
+        Rgba::from_channels(r, 0, 0, a)
+    });
+
+    let g_channel = ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        let x = clamping_add(x, config.g.current_shift_x as i32, img.width() - 1);
+        let y = clamping_add(y, config.g.current_shift_y as i32, img.height() - 1);
+
+        let (_, g, _, a) = img.get_pixel(x, y).channels4();
+        Rgba::from_channels(0, g, 0, a)
+    });
+
+    let b_channel = ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        let x = clamping_add(x, config.b.current_shift_x as i32, img.width() - 1);
+        let y = clamping_add(y, config.b.current_shift_y as i32, img.height() - 1);
+
+        let (_, _, b, a) = img.get_pixel(x, y).channels4();
+        Rgba::from_channels(0, 0, b, a)
+    });
+
+    ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        let p1 = r_channel.get_pixel(x, y);
+        let p2 = g_channel.get_pixel(x, y);
+        let p3 = b_channel.get_pixel(x, y);
+        blend(&[p1, p2, p3])
+    })
+}
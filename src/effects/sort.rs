@@ -0,0 +1,223 @@
+use image::{Rgba, Pixel};
+use rand::Rng;
+use std::str::FromStr;
+use effects::{Effect, Params, parse_param, unknown_param};
+use util::{GlitchRng, RgbaBuf};
+
+/// Whether pixels are sorted within rows or within columns
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Rows,
+    Columns,
+}
+
+impl FromStr for SortDirection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SortDirection, String> {
+        match s {
+            "rows" => Ok(SortDirection::Rows),
+            "columns" => Ok(SortDirection::Columns),
+            _ => Err(format!("Unknown sort direction '{}', expected rows or columns", s)),
+        }
+    }
+}
+
+/// The property pixels are sorted by
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    Luminance,
+    Hue,
+    Saturation,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SortKey, String> {
+        match s {
+            "luminance" => Ok(SortKey::Luminance),
+            "hue" => Ok(SortKey::Hue),
+            "saturation" => Ok(SortKey::Saturation),
+            _ => Err(format!("Unknown sort key '{}', expected luminance, hue or saturation", s)),
+        }
+    }
+}
+
+impl SortKey {
+    /// The sort value of a pixel, between 0 and 1
+    fn value(&self, px: &Rgba<u8>) -> f64 {
+        let (r, g, b, _) = px.channels4();
+        let (r, g, b) = (r as f64 / u8::MAX as f64, g as f64 / u8::MAX as f64, b as f64 / u8::MAX as f64);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+
+        match *self {
+            SortKey::Luminance => 0.2126 * r + 0.7152 * g + 0.0722 * b,
+            SortKey::Saturation => if max == 0.0 { 0.0 } else { (max - min) / max },
+            SortKey::Hue => {
+                let delta = max - min;
+                let hue = if delta == 0.0 {
+                    0.0
+                } else if max == r {
+                    ((g - b) / delta).rem_euclid(6.0)
+                } else if max == g {
+                    (b - r) / delta + 2.0
+                } else {
+                    (r - g) / delta + 4.0
+                };
+                hue / 6.0
+            },
+        }
+    }
+}
+
+/// Options for the pixel sorting effect
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct SortOptions {
+    pub direction: SortDirection,
+    pub key: SortKey,
+    /// Pixels with a key between `lower` and `upper` form the intervals that are sorted
+    pub lower: f64,
+    pub upper: f64,
+    /// Sort the pixels outside of the thresholds instead
+    pub invert: bool,
+    /// Only pixels with a luminance in this range are sorted, whatever the key. Others stay in place
+    /// and end intervals.
+    pub min_luminance: f64,
+    pub max_luminance: f64,
+    /// Maximum length of an interval. Intervals are cut into pieces of random length up to this. 0 for no limit.
+    pub length: u32,
+    /// Sort descending instead of ascending
+    pub reverse: bool,
+}
+
+impl Default for SortOptions {
+    fn default() -> SortOptions {
+        SortOptions {
+            direction: SortDirection::Rows,
+            key: SortKey::Luminance,
+            lower: 0.25,
+            upper: 0.8,
+            invert: false,
+            min_luminance: 0.0,
+            max_luminance: 1.0,
+            length: 0,
+            reverse: false,
+        }
+    }
+}
+
+impl Params for SortOptions {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "direction" => self.direction = parse_param(key, value)?,
+            "key" => self.key = parse_param(key, value)?,
+            "lower" => self.lower = parse_param(key, value)?,
+            "upper" => self.upper = parse_param(key, value)?,
+            "invert" => self.invert = parse_param(key, value)?,
+            "min-luminance" => self.min_luminance = parse_param(key, value)?,
+            "max-luminance" => self.max_luminance = parse_param(key, value)?,
+            "length" => self.length = parse_param(key, value)?,
+            "reverse" => self.reverse = parse_param(key, value)?,
+            _ => return unknown_param(key),
+        }
+        Ok(())
+    }
+}
+
+impl Effect for SortOptions {
+    fn apply(&self, img: &RgbaBuf, rng: &mut GlitchRng) -> RgbaBuf {
+        sort(img, self, rng)
+    }
+
+    fn step(&self, _: &mut GlitchRng) -> Box<dyn Effect> {
+        Box::new(*self)
+    }
+}
+
+fn sort<R: Rng>(img: &RgbaBuf, config: &SortOptions, rng: &mut R) -> RgbaBuf {
+    println!("* Sorting pixels");
+
+    let mut img = img.clone();
+    let (lines, line_length) = match config.direction {
+        SortDirection::Rows => (img.height(), img.width()),
+        SortDirection::Columns => (img.width(), img.height()),
+    };
+    let position = |line: u32, i: u32| match config.direction {
+        SortDirection::Rows => (i, line),
+        SortDirection::Columns => (line, i),
+    };
+
+    for line in 0..lines {
+        let pixels = (0..line_length)
+            .map(|i| {
+                let (x, y) = position(line, i);
+                *img.get_pixel(x, y)
+            })
+            .collect::<Vec<_>>();
+
+        let mut sorted = pixels.clone();
+        for (start, end) in intervals(&pixels, config, rng) {
+            let interval = &mut sorted[start..end];
+            interval.sort_by(|a, b| {
+                let order = config.key.value(a).partial_cmp(&config.key.value(b)).unwrap();
+                if config.reverse { order.reverse() } else { order }
+            });
+        }
+
+        for (i, px) in sorted.into_iter().enumerate() {
+            let (x, y) = position(line, i as u32);
+            img.put_pixel(x, y, px);
+        }
+    }
+
+    img
+}
+
+/// Ranges of consecutive pixels to sort
+fn intervals<R: Rng>(pixels: &[Rgba<u8>], config: &SortOptions, rng: &mut R) -> Vec<(usize, usize)> {
+    // Transparent pixels and those masked out by their luminance are never moved
+    let sortable = |px: &Rgba<u8>| {
+        let value = config.key.value(px);
+        let inside = value >= config.lower && value <= config.upper;
+        let luminance = SortKey::Luminance.value(px);
+        let masked = luminance < config.min_luminance || luminance > config.max_luminance;
+        px.channels()[3] > 0 && !masked && inside != config.invert
+    };
+
+    let mut intervals = vec![];
+    let mut start = None;
+    for (i, px) in pixels.iter().enumerate() {
+        match (start, sortable(px)) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                intervals.push((s, i));
+                start = None;
+            },
+            _ => {},
+        }
+    }
+    if let Some(s) = start {
+        intervals.push((s, pixels.len()));
+    }
+
+    if config.length == 0 {
+        return intervals;
+    }
+
+    let mut pieces = vec![];
+    for (mut start, end) in intervals {
+        while start < end {
+            let length = rng.gen_range(1, config.length as usize + 1);
+            let piece_end = usize::min(start + length, end);
+            pieces.push((start, piece_end));
+            start = piece_end;
+        }
+    }
+    pieces
+}
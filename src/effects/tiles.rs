@@ -0,0 +1,298 @@
+use image::ImageBuffer;
+use rand::{Rng, sample};
+use std::str::FromStr;
+use effects::{Effect, Params, RenderInfo, parse_param, unknown_param};
+use util::{GlitchRng, RgbaBuf, RgbaImage, seeded_rng};
+
+/// How the image is cut into tiles
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TileLayout {
+    /// Equally sized cells of `columns` × `rows`
+    Grid,
+    /// `rows` full-width strips of random height
+    Strips,
+}
+
+impl FromStr for TileLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TileLayout, String> {
+        match s {
+            "grid" => Ok(TileLayout::Grid),
+            "strips" => Ok(TileLayout::Strips),
+            _ => Err(format!("Unknown tile layout '{}', expected grid or strips", s)),
+        }
+    }
+}
+
+/// What happens to the scrambled tiles
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TileMode {
+    /// Swap the contents of the tiles among each other
+    Permute,
+    /// Replace each tile with a copy of a random tile
+    Duplicate,
+    /// Flip each tile horizontally or vertically
+    Mirror,
+    /// Turn each tile by a random multiple of 90 degrees, stretching it to fit
+    Rotate,
+    /// Pick one of the above for each tile
+    Mixed,
+}
+
+impl FromStr for TileMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TileMode, String> {
+        match s {
+            "permute" => Ok(TileMode::Permute),
+            "duplicate" => Ok(TileMode::Duplicate),
+            "mirror" => Ok(TileMode::Mirror),
+            "rotate" => Ok(TileMode::Rotate),
+            "mixed" => Ok(TileMode::Mixed),
+            _ => Err(format!("Unknown tile mode '{}', expected permute, duplicate, mirror, rotate or mixed", s)),
+        }
+    }
+}
+
+/// Options for the tile scramble effect
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct TileOptions {
+    pub layout: TileLayout,
+    pub columns: u32,
+    pub rows: u32,
+    pub mode: TileMode,
+    /// Fraction of the tiles that are scrambled, from 0 to 1
+    pub fraction: f64,
+    /// Number of frames after which the tiles are scrambled anew. 0 keeps the first arrangement.
+    pub reshuffle: usize,
+    /// Distance in pixels the contents of scrambled tiles slide to the right each frame
+    pub slide: f64,
+}
+
+impl Default for TileOptions {
+    fn default() -> TileOptions {
+        TileOptions {
+            layout: TileLayout::Grid,
+            columns: 8,
+            rows: 6,
+            mode: TileMode::Permute,
+            fraction: 0.3,
+            reshuffle: 1,
+            slide: 0.0,
+        }
+    }
+}
+
+impl Params for TileOptions {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "layout" => self.layout = parse_param(key, value)?,
+            "columns" => self.columns = parse_param(key, value)?,
+            "rows" => self.rows = parse_param(key, value)?,
+            "mode" => self.mode = parse_param(key, value)?,
+            "fraction" => self.fraction = parse_param(key, value)?,
+            "reshuffle" => self.reshuffle = parse_param(key, value)?,
+            "slide" => self.slide = parse_param(key, value)?,
+            _ => return unknown_param(key),
+        }
+        Ok(())
+    }
+}
+
+/// The tile scramble effect
+#[derive(Copy, Clone)]
+pub struct TileScramble {
+    options: TileOptions,
+    seed: u32,
+    frame: usize,
+    period: Option<usize>,
+}
+
+#[derive(Copy, Clone)]
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Where the pixels of a scrambled tile come from
+#[derive(Copy, Clone)]
+struct TileSource {
+    tile: usize,
+    flip_x: bool,
+    flip_y: bool,
+    /// Number of quarter turns clockwise
+    turns: u8,
+}
+
+impl TileScramble {
+    pub fn random<R: Rng>(options: &TileOptions, info: &RenderInfo, rng: &mut R) -> TileScramble {
+        TileScramble {
+            options: *options,
+            seed: rng.next_u32(),
+            frame: 0,
+            period: info.period,
+        }
+    }
+
+    /// Seed of the tile arrangement of the current frame
+    fn arrangement_seed(&self) -> u64 {
+        let frame = self.period.map_or(self.frame, |period| self.frame % period);
+        let shuffle = frame.checked_div(self.options.reshuffle).unwrap_or(0);
+        self.seed as u64 + shuffle as u64 * 7
+    }
+
+    /// Distance the contents of scrambled tiles have slid since the first frame
+    fn slide_offset(&self, width: u32) -> i64 {
+        let speed = match self.period {
+            // Slide across the image a whole number of times per period
+            Some(period) => (self.options.slide * period as f64 / width as f64).round() * width as f64 / period as f64,
+            None => self.options.slide,
+        };
+        (speed * self.frame as f64).round() as i64
+    }
+}
+
+impl Params for TileScramble {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        self.options.set_param(key, value)
+    }
+}
+
+impl Effect for TileScramble {
+    fn apply(&self, img: &RgbaBuf, _: &mut GlitchRng) -> RgbaBuf {
+        scramble_tiles(img, self)
+    }
+
+    fn step(&self, _: &mut GlitchRng) -> Box<dyn Effect> {
+        Box::new(TileScramble {
+            frame: match self.period {
+                Some(period) => (self.frame + 1) % period,
+                None => self.frame + 1,
+            },
+            ..*self
+        })
+    }
+}
+
+fn scramble_tiles<T: RgbaImage>(img: &T, config: &TileScramble) -> RgbaBuf {
+    println!("* Scrambling tiles");
+
+    let opts = &config.options;
+    let (width, height) = (img.width(), img.height());
+    let mut rng = seeded_rng(config.arrangement_seed());
+    let tiles = cut_tiles(opts, width, height, &mut rng);
+    let sources = scramble(opts, tiles.len(), &mut rng);
+    let offset = config.slide_offset(width);
+
+    // Index of the tile covering each pixel
+    let mut tile_map = vec![0; (width * height) as usize];
+    for (i, tile) in tiles.iter().enumerate() {
+        for y in tile.y..tile.y + tile.height {
+            for x in tile.x..tile.x + tile.width {
+                tile_map[(y * width + x) as usize] = i;
+            }
+        }
+    }
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let i = tile_map[(y * width + x) as usize];
+        let source = match sources[i] {
+            Some(source) => source,
+            None => return img.get_pixel(x, y),
+        };
+
+        // Position inside the tile, from 0 to 1
+        let tile = tiles[i];
+        let u = (x - tile.x) as f64 / tile.width as f64;
+        let v = (y - tile.y) as f64 / tile.height as f64;
+        let u = if source.flip_x { 1.0 - 1.0 / tile.width as f64 - u } else { u };
+        let v = if source.flip_y { 1.0 - 1.0 / tile.height as f64 - v } else { v };
+        let (u, v) = match source.turns % 4 {
+            1 => (v, 1.0 - 1.0 / tile.width as f64 - u),
+            2 => (1.0 - 1.0 / tile.width as f64 - u, 1.0 - 1.0 / tile.height as f64 - v),
+            3 => (1.0 - 1.0 / tile.height as f64 - v, u),
+            _ => (u, v),
+        };
+
+        let from = tiles[source.tile];
+        let source_x = from.x + u32::min(from.width - 1, (u * from.width as f64) as u32);
+        let source_y = from.y + u32::min(from.height - 1, (v * from.height as f64) as u32);
+        let source_x = (source_x as i64 - offset).rem_euclid(width as i64) as u32;
+        img.get_pixel(source_x, source_y)
+    })
+}
+
+/// Cut the image into non-overlapping tiles covering all of it
+fn cut_tiles<R: Rng>(opts: &TileOptions, width: u32, height: u32, rng: &mut R) -> Vec<Tile> {
+    let rows = u32::max(1, u32::min(height, opts.rows));
+    let row_edges = match opts.layout {
+        TileLayout::Grid => (0..=rows).map(|i| i * height / rows).collect::<Vec<_>>(),
+        TileLayout::Strips => {
+            let mut edges = sample(rng, 1..height, (rows - 1) as usize);
+            edges.push(0);
+            edges.push(height);
+            edges.sort();
+            edges
+        },
+    };
+    let columns = match opts.layout {
+        TileLayout::Grid => u32::max(1, u32::min(width, opts.columns)),
+        TileLayout::Strips => 1,
+    };
+
+    let mut tiles = vec!();
+    for row in row_edges.windows(2) {
+        for column in 0..columns {
+            let (x, next_x) = (column * width / columns, (column + 1) * width / columns);
+            tiles.push(Tile { x, y: row[0], width: next_x - x, height: row[1] - row[0] });
+        }
+    }
+    tiles.retain(|tile| tile.width > 0 && tile.height > 0);
+    tiles
+}
+
+/// Pick a fraction of the tiles and decide where their pixels come from
+fn scramble<R: Rng>(opts: &TileOptions, count: usize, rng: &mut R) -> Vec<Option<TileSource>> {
+    let mut sources = vec![None; count];
+    let mut indices = (0..count).collect::<Vec<_>>();
+    rng.shuffle(&mut indices);
+    let selected = &indices[..(opts.fraction.clamp(0.0, 1.0) * count as f64).round() as usize];
+
+    let mut permuted = vec!();
+    for &i in selected {
+        let mode = match opts.mode {
+            TileMode::Mixed => *rng.choose(&[TileMode::Permute, TileMode::Duplicate, TileMode::Mirror, TileMode::Rotate]).unwrap(),
+            mode => mode,
+        };
+        let unchanged = TileSource { tile: i, flip_x: false, flip_y: false, turns: 0 };
+        sources[i] = Some(match mode {
+            TileMode::Duplicate => TileSource { tile: rng.gen_range(0, count), ..unchanged },
+            TileMode::Mirror => {
+                let horizontal = rng.gen();
+                TileSource { flip_x: horizontal, flip_y: !horizontal, ..unchanged }
+            },
+            TileMode::Rotate => TileSource { turns: rng.gen_range(1, 4), ..unchanged },
+            _ => {
+                permuted.push(i);
+                unchanged
+            },
+        });
+    }
+
+    // Hand the contents of the permuted tiles around in a cycle, so none stays in place
+    if permuted.len() > 1 {
+        for (k, &i) in permuted.iter().enumerate() {
+            if let Some(ref mut source) = sources[i] {
+                source.tile = permuted[(k + 1) % permuted.len()];
+            }
+        }
+    }
+    sources
+}
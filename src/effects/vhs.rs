@@ -0,0 +1,193 @@
+use image::{ImageBuffer, Rgba, Pixel};
+use rand::Rng;
+use std::f64::consts::PI;
+use effects::{Effect, Params, RenderInfo, parse_param, unknown_param};
+use util::{GlitchRng, RgbaBuf, RgbaImage, clamping_add, rgb_to_yiq, yiq_to_rgb};
+
+/// Options for the VHS tape effect
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct VhsOptions {
+    /// Height of the tracking bar as a fraction of the image height
+    pub bar_height: f64,
+    /// Distance the tracking bar rolls down each frame, as a fraction of the image height
+    pub bar_speed: f64,
+    /// Maximum horizontal displacement of lines inside the tracking bar
+    pub jitter: f64,
+    /// Strength of the static inside the tracking bar, from 0 to 1
+    pub noise: f64,
+    /// Number of lines at the bottom distorted by head switching
+    pub head_rows: u32,
+    /// Horizontal displacement of the bottom line by head switching
+    pub head_shift: f64,
+    /// Distance in pixels color bleeds to the right
+    pub bleed: f64,
+}
+
+impl Default for VhsOptions {
+    fn default() -> VhsOptions {
+        VhsOptions {
+            bar_height: 0.1,
+            bar_speed: 0.04,
+            jitter: 8.0,
+            noise: 0.3,
+            head_rows: 10,
+            head_shift: 20.0,
+            bleed: 4.0,
+        }
+    }
+}
+
+impl Params for VhsOptions {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "bar-height" => self.bar_height = parse_param(key, value)?,
+            "bar-speed" => self.bar_speed = parse_param(key, value)?,
+            "jitter" => self.jitter = parse_param(key, value)?,
+            "noise" => self.noise = parse_param(key, value)?,
+            "head-rows" => self.head_rows = parse_param(key, value)?,
+            "head-shift" => self.head_shift = parse_param(key, value)?,
+            "bleed" => self.bleed = parse_param(key, value)?,
+            _ => return unknown_param(key),
+        }
+        Ok(())
+    }
+}
+
+/// The VHS tape effect
+#[derive(Copy, Clone)]
+pub struct Vhs {
+    options: VhsOptions,
+    /// Position of the tracking bar in the first frame, as a fraction of the image height
+    start: f64,
+    frame: usize,
+    period: Option<usize>,
+}
+
+impl Vhs {
+    pub fn random<R: Rng>(options: &VhsOptions, info: &RenderInfo, rng: &mut R) -> Vhs {
+        Vhs {
+            options: *options,
+            start: rng.next_f64(),
+            frame: 0,
+            period: info.period,
+        }
+    }
+
+    /// Position of the center of the tracking bar, as a fraction of the image height
+    fn bar_position(&self) -> f64 {
+        let speed = match self.period {
+            // Roll through the image a whole number of times per period
+            Some(period) => (self.options.bar_speed * period as f64).round() / period as f64,
+            None => self.options.bar_speed,
+        };
+        // Negative speeds roll the bar up
+        (self.start + speed * self.frame as f64).rem_euclid(1.0)
+    }
+}
+
+impl Params for Vhs {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        self.options.set_param(key, value)
+    }
+}
+
+impl Effect for Vhs {
+    fn apply(&self, img: &RgbaBuf, rng: &mut GlitchRng) -> RgbaBuf {
+        vhs(img, self, rng)
+    }
+
+    fn step(&self, _: &mut GlitchRng) -> Box<dyn Effect> {
+        Box::new(Vhs {
+            frame: match self.period {
+                Some(period) => (self.frame + 1) % period,
+                None => self.frame + 1,
+            },
+            ..*self
+        })
+    }
+}
+
+fn vhs<T: RgbaImage, R: Rng>(img: &T, effect: &Vhs, rng: &mut R) -> RgbaBuf {
+    println!("* Playing back VHS tape");
+
+    let config = &effect.options;
+    let img = bleed_chroma(img, config.bleed);
+    let (width, height) = img.dimensions();
+
+    // Strength of the tracking error of each line, peaking at the center of the bar
+    let center = effect.bar_position() * height as f64;
+    let half_height = f64::max(0.5, config.bar_height * height as f64 / 2.0);
+    let bar_strength = (0..height)
+        .map(|y| {
+            // The bar wraps around from the bottom to the top
+            let distance = (y as f64 - center).abs();
+            let distance = f64::min(distance, height as f64 - distance);
+            if distance < half_height {
+                f64::cos(distance / half_height * PI / 2.0)
+            } else {
+                0.0
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let head_start = height.saturating_sub(config.head_rows);
+    let line_shift = (0..height)
+        .map(|y| {
+            let jitter = bar_strength[y as usize] * config.jitter * (rng.next_f64() * 2.0 - 1.0);
+            let head = if y >= head_start {
+                // Distortion grows towards the bottom line
+                let t = (y - head_start + 1) as f64 / config.head_rows as f64;
+                config.head_shift * t * t * (0.8 + 0.4 * rng.next_f64())
+            } else {
+                0.0
+            };
+            (jitter + head).round() as i32
+        })
+        .collect::<Vec<_>>();
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let source_x = clamping_add(x, -line_shift[y as usize], width - 1);
+        let px = *img.get_pixel(source_x, y);
+
+        let static_strength = bar_strength[y as usize] * config.noise;
+        if static_strength > 0.0 && rng.next_f64() < static_strength {
+            let (r, g, b, a) = px.channels4();
+            // Tape static is mostly bright
+            let value = (0.5 + 0.5 * rng.next_f64()) * u8::MAX as f64;
+            let mix = |c: u8| (c as f64 + (value - c as f64) * static_strength) as u8;
+            Rgba::from_channels(mix(r), mix(g), mix(b), a)
+        } else {
+            px
+        }
+    })
+}
+
+/// Smear the color, but not the brightness, of each line to the right
+fn bleed_chroma<T: RgbaImage>(img: &T, distance: f64) -> RgbaBuf {
+    let mut output = ImageBuffer::from_fn(img.width(), img.height(), |x, y| img.get_pixel(x, y));
+    if distance <= 0.0 {
+        return output;
+    }
+
+    let smoothing = 1.0 / (1.0 + distance);
+    let max = u8::MAX as f64;
+    for y in 0..img.height() {
+        let (mut i_acc, mut q_acc) = (0.0, 0.0);
+        for x in 0..img.width() {
+            let (r, g, b, a) = img.get_pixel(x, y).channels4();
+            let (luma, i, q) = rgb_to_yiq(r as f64 / max, g as f64 / max, b as f64 / max);
+            if x == 0 {
+                i_acc = i;
+                q_acc = q;
+            }
+            i_acc += (i - i_acc) * smoothing;
+            q_acc += (q - q_acc) * smoothing;
+
+            let (r, g, b) = yiq_to_rgb(luma, i_acc, q_acc);
+            output.put_pixel(x, y, Rgba::from_channels((r * max).round() as u8, (g * max).round() as u8, (b * max).round() as u8, a));
+        }
+    }
+    output
+}
@@ -0,0 +1,248 @@
+use image::{ImageBuffer, Rgba, Pixel};
+use rand::Rng;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use effects::{Effect, Params, parse_param, unknown_param};
+use util::{GlitchRng, RgbaBuf, RgbaImage};
+
+/// The direction wind streaks extend in, written as `left`, `right`, `up`, `down` or an angle in
+/// degrees counterclockwise from `right`
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum WindDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+    Angle(f64),
+}
+
+impl WindDirection {
+    /// Direction as a unit vector in image coordinates, with y pointing down
+    fn vector(&self) -> (f64, f64) {
+        match *self {
+            WindDirection::Left => (-1.0, 0.0),
+            WindDirection::Right => (1.0, 0.0),
+            WindDirection::Up => (0.0, -1.0),
+            WindDirection::Down => (0.0, 1.0),
+            WindDirection::Angle(degrees) => {
+                let radians = degrees.to_radians();
+                (radians.cos(), -radians.sin())
+            },
+        }
+    }
+}
+
+impl FromStr for WindDirection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<WindDirection, String> {
+        match s {
+            "left" => Ok(WindDirection::Left),
+            "right" => Ok(WindDirection::Right),
+            "up" => Ok(WindDirection::Up),
+            "down" => Ok(WindDirection::Down),
+            _ => match s.parse::<f64>() {
+                Ok(degrees) if degrees.is_finite() => Ok(WindDirection::Angle(degrees)),
+                _ => Err(format!("Unknown wind direction '{}', expected left, right, up, down or an angle in degrees", s)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for WindDirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WindDirection::Left => write!(f, "left"),
+            WindDirection::Right => write!(f, "right"),
+            WindDirection::Up => write!(f, "up"),
+            WindDirection::Down => write!(f, "down"),
+            WindDirection::Angle(degrees) => write!(f, "{}", degrees),
+        }
+    }
+}
+
+impl From<WindDirection> for String {
+    fn from(direction: WindDirection) -> String {
+        direction.to_string()
+    }
+}
+
+impl TryFrom<String> for WindDirection {
+    type Error = String;
+
+    fn try_from(spec: String) -> Result<WindDirection, String> {
+        spec.parse()
+    }
+}
+
+/// How streaks fade out along their length
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindFade {
+    /// The streak becomes transparent
+    Alpha,
+    /// The streak mixes with the pixels it covers
+    Blend,
+}
+
+impl FromStr for WindFade {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<WindFade, String> {
+        match s {
+            "alpha" => Ok(WindFade::Alpha),
+            "blend" => Ok(WindFade::Blend),
+            _ => Err(format!("Unknown wind fade '{}', expected alpha or blend", s)),
+        }
+    }
+}
+
+/// Options for the wind (line stretching) effect
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct WindOptions {
+    #[serde(rename = "onset")]
+    pub wind_onset_chance: f32,
+    #[serde(rename = "continue")]
+    pub wind_stop_chance: f32,
+    pub direction: WindDirection,
+    /// Length in pixels after which a streak has completely faded out. 0 disables fading.
+    pub fade: f64,
+    pub fade_mode: WindFade,
+}
+
+impl WindOptions {
+    pub fn new(wind_onset_chance: f32, wind_stop_chance: f32) -> WindOptions {
+        WindOptions {
+            wind_onset_chance,
+            wind_stop_chance,
+            direction: WindDirection::Left,
+            fade: 0.0,
+            fade_mode: WindFade::Blend,
+        }
+    }
+}
+
+impl Default for WindOptions {
+    fn default() -> WindOptions {
+        WindOptions::new(0.05, 0.15)
+    }
+}
+
+impl Params for WindOptions {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "onset" => self.wind_onset_chance = parse_param(key, value)?,
+            "continue" => self.wind_stop_chance = parse_param(key, value)?,
+            "direction" => self.direction = parse_param(key, value)?,
+            "fade" => self.fade = parse_param(key, value)?,
+            "fade-mode" => self.fade_mode = parse_param(key, value)?,
+            _ => return unknown_param(key),
+        }
+        Ok(())
+    }
+}
+
+impl Effect for WindOptions {
+    fn apply(&self, img: &RgbaBuf, rng: &mut GlitchRng) -> RgbaBuf {
+        wind(img, self, rng)
+    }
+
+    fn step(&self, _: &mut GlitchRng) -> Box<dyn Effect> {
+        Box::new(*self)
+    }
+}
+
+fn wind<T: RgbaImage, R: Rng>(img: &T, config: &WindOptions, rng: &mut R) -> RgbaBuf {
+    println!("* Applying wind effect");
+
+    let mut img = ImageBuffer::from_fn(img.width(), img.height(), |x, y| img.get_pixel(x, y));
+    for line in lines(img.dimensions(), config.direction.vector()) {
+        // Color of the current streak and the number of pixels it already covers
+        let mut streak: Option<(Rgba<u8>, u32)> = None;
+        for (x, y) in line {
+            match streak {
+                Some((color, length)) => {
+                    let strength = if config.fade > 0.0 {
+                        1.0 - (length + 1) as f64 / config.fade
+                    } else {
+                        1.0
+                    };
+                    if strength <= 0.0 {
+                        streak = None;
+                        continue;
+                    }
+
+                    img[(x, y)] = fade(color, img[(x, y)], strength, config.fade_mode);
+                    streak = if rng.next_f32() < config.wind_stop_chance {
+                        None
+                    } else {
+                        Some((color, length + 1))
+                    };
+                },
+                None => {
+                    let color = img[(x, y)];
+                    if color.channels4().3 > 0 && rng.next_f32() < config.wind_onset_chance {
+                        streak = Some((color, 0));
+                    }
+                },
+            }
+        }
+    }
+
+    img
+}
+
+/// The color of a streak at the given strength, from 0 to 1, over the pixel it covers
+fn fade(streak: Rgba<u8>, covered: Rgba<u8>, strength: f64, mode: WindFade) -> Rgba<u8> {
+    if strength >= 1.0 {
+        return streak;
+    }
+
+    let (r, g, b, a) = streak.channels4();
+    match mode {
+        WindFade::Alpha => Rgba::from_channels(r, g, b, (a as f64 * strength).round() as u8),
+        WindFade::Blend => {
+            let (cr, cg, cb, ca) = covered.channels4();
+            let mix = |s: u8, c: u8| (c as f64 + (s as f64 - c as f64) * strength).round() as u8;
+            Rgba::from_channels(mix(r, cr), mix(g, cg), mix(b, cb), mix(a, ca))
+        },
+    }
+}
+
+/// Lines of pixel coordinates covering the image exactly once, each running in the given direction
+fn lines((width, height): (u32, u32), (dx, dy): (f64, f64)) -> Vec<Vec<(u32, u32)>> {
+    // Walk along the axis closer to the direction, drifting along the other one
+    let horizontal = dx.abs() >= dy.abs();
+    let (major_len, minor_len, major_dir, slope) = if horizontal {
+        (width as i64, height as i64, dx, dy / dx.abs())
+    } else {
+        (height as i64, width as i64, dy, dx / dy.abs())
+    };
+    if major_len == 0 || minor_len == 0 {
+        return Vec::new();
+    }
+
+    let drift = |t: i64| (slope * t as f64).round() as i64;
+    let end_drift = drift(major_len - 1);
+    let (min_drift, max_drift) = (i64::min(0, end_drift), i64::max(0, end_drift));
+
+    (-max_drift..minor_len - min_drift)
+        .map(|start| {
+            (0..major_len)
+                .filter_map(|t| {
+                    let major = if major_dir < 0.0 { major_len - 1 - t } else { t };
+                    let minor = start + drift(t);
+                    if minor < 0 || minor >= minor_len {
+                        return None;
+                    }
+                    Some(if horizontal { (major as u32, minor as u32) } else { (minor as u32, major as u32) })
+                })
+                .collect()
+        })
+        .filter(|line: &Vec<_>| !line.is_empty())
+        .collect()
+}
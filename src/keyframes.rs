@@ -0,0 +1,243 @@
+use std::convert::TryFrom;
+use std::fmt;
+use effects::{self, Effect};
+use layer::Layer;
+use options::Options;
+use pipeline::Stage;
+
+/// How values between two keyframes are computed
+#[derive(Copy, Clone, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    Ease,
+    Step,
+}
+
+impl Interpolation {
+    fn name(&self) -> &'static str {
+        match *self {
+            Interpolation::Linear => "linear",
+            Interpolation::Ease => "ease",
+            Interpolation::Step => "step",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Interpolation> {
+        match name {
+            "linear" => Some(Interpolation::Linear),
+            "ease" => Some(Interpolation::Ease),
+            "step" => Some(Interpolation::Step),
+            _ => None,
+        }
+    }
+
+    /// Map the progress between two keyframes onto the progress of the value
+    fn apply(&self, t: f64) -> f64 {
+        match *self {
+            Interpolation::Linear => t,
+            // Smoothstep
+            Interpolation::Ease => t * t * (3.0 - 2.0 * t),
+            Interpolation::Step => 0.0,
+        }
+    }
+}
+
+/// Keyframed values of a single effect parameter, e.g. `color-shift.distance:ease=0@0,12@30`
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Track {
+    pub effect: String,
+    pub param: String,
+    pub interpolation: Interpolation,
+    /// Pairs of frame and value, sorted by frame
+    pub keys: Vec<(usize, f64)>,
+}
+
+impl Track {
+    /// The parameter value at the given frame. Values are held before the first and after the last keyframe.
+    pub fn value_at(&self, frame: usize) -> f64 {
+        let next = self.keys.iter().position(|&(key_frame, _)| key_frame > frame);
+        match next {
+            Some(0) => self.keys[0].1,
+            Some(i) => {
+                let (start_frame, start) = self.keys[i - 1];
+                let (end_frame, end) = self.keys[i];
+                let t = (frame - start_frame) as f64 / (end_frame - start_frame) as f64;
+                start + (end - start) * self.interpolation.apply(t)
+            },
+            None => self.keys[self.keys.len() - 1].1,
+        }
+    }
+}
+
+impl fmt::Display for Track {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.effect, self.param)?;
+        if self.interpolation != Interpolation::Linear {
+            write!(f, ":{}", self.interpolation.name())?;
+        }
+        let keys = self.keys.iter()
+            .map(|(frame, value)| format!("{}@{}", value, frame))
+            .collect::<Vec<_>>();
+        write!(f, "={}", keys.join(","))
+    }
+}
+
+impl From<Track> for String {
+    fn from(track: Track) -> String {
+        track.to_string()
+    }
+}
+
+impl TryFrom<String> for Track {
+    type Error = String;
+
+    fn try_from(spec: String) -> Result<Track, String> {
+        parse(&spec)
+    }
+}
+
+/// Parse a keyframe spec of the form `EFFECT.PARAM[:INTERPOLATION]=VALUE@FRAME,VALUE@FRAME,...`
+pub fn parse(spec: &str) -> Result<Track, String> {
+    let mut parts = spec.splitn(2, '=');
+    let target = parts.next().unwrap_or("").trim();
+    let keys = parts.next().ok_or_else(|| format!("Expected '=' in keyframes '{}'", spec))?;
+
+    let mut target_parts = target.splitn(2, ':');
+    let param = target_parts.next().unwrap_or("");
+    let interpolation = match target_parts.next() {
+        Some(name) => Interpolation::from_name(name.trim())
+            .ok_or_else(|| format!("Unknown interpolation '{}', expected linear, ease or step", name))?,
+        None => Interpolation::Linear,
+    };
+
+    let mut param_parts = param.splitn(2, '.');
+    let effect = param_parts.next().unwrap_or("").trim();
+    let param = param_parts.next()
+        .ok_or_else(|| format!("Expected 'effect.parameter' in keyframes '{}'", spec))?
+        .trim();
+
+    let info = effects::lookup(effect).ok_or_else(|| format!("Unknown effect '{}'", effect))?;
+    // Layer parameters belong to the stage rather than the effect
+    let checked = if Layer::is_layer_param(param) {
+        Layer::check_keyframe_param(param)
+    } else {
+        (info.params)(&mut Options::default()).set_param(param, "0")
+    };
+    checked.map_err(|e| format!("{} of effect '{}'", e, effect))?;
+
+    let mut parsed_keys = vec![];
+    for key in keys.split(',').map(str::trim) {
+        let mut key_parts = key.splitn(2, '@');
+        let value = key_parts.next().unwrap_or("").trim();
+        let frame = key_parts.next()
+            .ok_or_else(|| format!("Expected 'value@frame', found '{}'", key))?
+            .trim();
+        let value = value.parse::<f64>().map_err(|_| format!("Invalid keyframe value '{}'", value))?;
+        let frame = frame.parse::<usize>().map_err(|_| format!("Invalid keyframe frame '{}'", frame))?;
+        parsed_keys.push((frame, value));
+    }
+    parsed_keys.sort_by_key(|&(frame, _)| frame);
+    if parsed_keys.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+        return Err(format!("Duplicate frame in keyframes '{}'", spec));
+    }
+
+    Ok(Track {
+        effect: String::from(effect),
+        param: String::from(param),
+        interpolation,
+        keys: parsed_keys,
+    })
+}
+
+/// Add the keyframed values at the given frame to the parameters of all matching stages
+pub fn apply_to_stages(tracks: &[Track], frame: usize, stages: &[Stage]) -> Vec<Stage> {
+    let mut stages = stages.to_vec();
+    for track in tracks {
+        for stage in stages.iter_mut().filter(|stage| stage.effect == track.effect) {
+            stage.params.push((track.param.clone(), track.value_at(frame).to_string()));
+        }
+    }
+    stages
+}
+
+/// Set the keyframed values at the given frame on all matching effects of a built pipeline
+pub fn apply(tracks: &[Track], frame: usize, stages: &[Stage], pipeline: &mut [Box<dyn Effect>]) -> Result<(), String> {
+    for track in tracks {
+        let value = track.value_at(frame).to_string();
+        for (stage, effect) in stages.iter().zip(pipeline.iter_mut()) {
+            if stage.effect == track.effect {
+                effect.set_param(&track.param, &value)
+                    .map_err(|e| format!("{} in stage '{}'", e, stage.effect))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_sorts_keys() {
+        let track = parse("color-shift.distance:ease = 12@30, 0@0").unwrap();
+        assert_eq!(track.effect, "color-shift");
+        assert_eq!(track.param, "distance");
+        assert!(track.interpolation == Interpolation::Ease);
+        assert_eq!(track.keys, [(0, 0.0), (30, 12.0)]);
+        assert!(parse("wind.onset=0.1@0").unwrap().interpolation == Interpolation::Linear);
+    }
+
+    #[test]
+    fn rejects_malformed_specs() {
+        assert!(parse("wind.onset").err().unwrap().contains("Expected '='"));
+        assert!(parse("wind=1@0").err().unwrap().contains("Expected 'effect.parameter'"));
+        assert!(parse("wind.onset:cubic=1@0").err().unwrap().contains("Unknown interpolation"));
+        assert!(parse("tornado.speed=1@0").err().unwrap().contains("Unknown effect"));
+        assert!(parse("wind.speed=1@0").err().unwrap().contains("Unknown parameter 'speed'"));
+        assert!(parse("wind.onset=1").err().unwrap().contains("Expected 'value@frame'"));
+        assert!(parse("wind.onset=x@0").err().unwrap().contains("Invalid keyframe value"));
+        assert!(parse("wind.onset=1@-1").err().unwrap().contains("Invalid keyframe frame"));
+        assert!(parse("wind.onset=1@3,2@3").err().unwrap().contains("Duplicate frame"));
+    }
+
+    #[test]
+    fn interpolates_between_keys() {
+        let track = parse("color-shift.distance=0@10,10@20,4@30").unwrap();
+        assert_eq!(track.value_at(0), 0.0);
+        assert_eq!(track.value_at(10), 0.0);
+        assert_eq!(track.value_at(15), 5.0);
+        assert_eq!(track.value_at(20), 10.0);
+        assert_eq!(track.value_at(25), 7.0);
+        assert_eq!(track.value_at(30), 4.0);
+        assert_eq!(track.value_at(100), 4.0);
+    }
+
+    #[test]
+    fn eases_and_steps_between_keys() {
+        let ease = parse("color-shift.distance:ease=0@0,10@10").unwrap();
+        assert!(ease.value_at(2) < 2.0);
+        assert_eq!(ease.value_at(5), 5.0);
+        assert!(ease.value_at(8) > 8.0);
+
+        let step = parse("color-shift.distance:step=0@0,10@10").unwrap();
+        assert_eq!(step.value_at(9), 0.0);
+        assert_eq!(step.value_at(10), 10.0);
+    }
+
+    #[test]
+    fn tracks_round_trip_through_strings() {
+        let spec = "wind.onset:step=0.1@0,0.5@10";
+        assert_eq!(String::from(parse(spec).unwrap()), spec);
+    }
+
+    #[test]
+    fn keyframed_params_are_routed_like_stage_params() {
+        assert!(parse("crt.mask-strength=0@0,1@3").is_ok());
+        assert!(parse("crt.opacity=0@0,1@3").is_ok());
+        assert!(parse("crt.region-feather=0@0,8@3").is_ok());
+        assert!(parse("crt.region=0@0").err().unwrap().contains("Non-numeric parameter 'region'"));
+        assert!(parse("crt.mask=0@0").is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use image::{ImageBuffer, Rgba, Pixel};
+use rand::Rng;
+use std::str::FromStr;
+use effects::{Effect, Params, parse_param};
+use mask::MaskShape;
+use util::{GlitchRng, RgbaBuf, blend, blend_weighted, box_blur};
+
+/// How the result of a stage is combined with the image it was applied to
+#[derive(Copy, Clone, PartialEq)]
+pub enum BlendMode {
+    /// Replace the image
+    Normal,
+    /// Mix colors like light, as in the scanline gaps
+    Light,
+    /// Brighten by the inverse product of the inverted colors
+    Screen,
+    Add,
+    Multiply,
+    Difference,
+    Lighten,
+    Darken,
+}
+
+impl FromStr for BlendMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BlendMode, String> {
+        match s {
+            "normal" => Ok(BlendMode::Normal),
+            "light" => Ok(BlendMode::Light),
+            "screen" => Ok(BlendMode::Screen),
+            "add" => Ok(BlendMode::Add),
+            "multiply" => Ok(BlendMode::Multiply),
+            "difference" => Ok(BlendMode::Difference),
+            "lighten" => Ok(BlendMode::Lighten),
+            "darken" => Ok(BlendMode::Darken),
+            _ => Err(format!("Unknown blend mode '{}', expected normal, light, screen, add, multiply, difference, lighten or darken", s)),
+        }
+    }
+}
+
+impl BlendMode {
+    /// Combine `layer` with `base`, weighted from 0 (only `base`) to 1 (fully combined)
+    pub fn mix(&self, base: Rgba<u8>, layer: Rgba<u8>, weight: f64) -> Rgba<u8> {
+        if weight <= 0.0 {
+            return base;
+        }
+
+        // Light mixing depends on the alpha of both pixels, so it combines whole pixels
+        let light = if *self == BlendMode::Light { Some(blend(&[base, layer])) } else { None };
+
+        let weight = f64::min(1.0, weight);
+        let max = u8::MAX as f64;
+        let mut channels = [0u8; 4];
+        for (i, channel) in channels.iter_mut().enumerate() {
+            let (b, l) = (base.channels()[i] as f64 / max, layer.channels()[i] as f64 / max);
+            // Alpha is interpolated in all modes
+            let combined = match light {
+                _ if i == 3 => l,
+                Some(light) => light.channels()[i] as f64 / max,
+                None => self.combine(b, l),
+            };
+            *channel = ((b + (combined - b) * weight) * max).round() as u8;
+        }
+        Rgba(channels)
+    }
+
+    /// Combine channel values between 0 and 1
+    fn combine(&self, base: f64, layer: f64) -> f64 {
+        match *self {
+            BlendMode::Normal | BlendMode::Light => layer,
+            BlendMode::Screen => 1.0 - (1.0 - base) * (1.0 - layer),
+            BlendMode::Add => f64::min(1.0, base + layer),
+            BlendMode::Multiply => base * layer,
+            BlendMode::Difference => (base - layer).abs(),
+            BlendMode::Lighten => f64::max(base, layer),
+            BlendMode::Darken => f64::min(base, layer),
+        }
+    }
+}
+
+/// An effect whose result is combined with the image it was applied to through an opacity, a
+/// blend mode and a mask confining it to parts of the frame
+pub struct Layer {
+    effect: Box<dyn Effect>,
+    opacity: f64,
+    mode: BlendMode,
+    shape: Option<MaskShape>,
+    /// Width in pixels of the soft transition at the edges of the mask
+    feather: u32,
+    invert: bool,
+    seed: u32,
+}
+
+impl Layer {
+    /// Wrap an effect into a layer configured by stage parameters
+    pub fn new<R: Rng>(effect: Box<dyn Effect>, params: &[(String, String)], rng: &mut R) -> Result<Layer, String> {
+        let mut layer = Layer {
+            effect,
+            opacity: 1.0,
+            mode: BlendMode::Normal,
+            shape: None,
+            feather: 0,
+            invert: false,
+            seed: rng.next_u32(),
+        };
+        for (key, value) in params {
+            layer.set_param(key, value)?;
+        }
+        Ok(layer)
+    }
+
+    /// Whether a stage parameter configures the layer rather than the effect. The region mask has
+    /// its own names, as effects such as `crt` have `mask` parameters of their own.
+    pub fn is_layer_param(key: &str) -> bool {
+        key == "opacity" || key == "blend-mode" || key == "region" || key.starts_with("region-")
+    }
+
+    /// Check that a layer parameter exists and takes numbers, so it can be keyframed
+    pub fn check_keyframe_param(key: &str) -> Result<(), String> {
+        match key {
+            "opacity" | "region-feather" => Ok(()),
+            "blend-mode" | "region" | "region-invert" => Err(format!("Non-numeric parameter '{}'", key)),
+            _ => Err(format!("Unknown parameter '{}'", key)),
+        }
+    }
+}
+
+impl Params for Layer {
+    fn set_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "opacity" => self.opacity = parse_param(key, value)?,
+            "blend-mode" => self.mode = parse_param(key, value)?,
+            "region" => self.shape = Some(MaskShape::parse(value, self.seed)?),
+            "region-feather" => self.feather = parse_param(key, value)?,
+            "region-invert" => self.invert = parse_param(key, value)?,
+            _ => return self.effect.set_param(key, value),
+        }
+        Ok(())
+    }
+}
+
+impl Effect for Layer {
+    fn apply(&self, img: &RgbaBuf, rng: &mut GlitchRng) -> RgbaBuf {
+        let output = self.effect.apply(img, rng);
+        let (width, height) = output.dimensions();
+        let mut mask = match self.shape {
+            Some(ref shape) => shape.render(width, height),
+            None => vec![1.0; (width * height) as usize],
+        };
+        if self.feather > 0 {
+            // Soften the edges by averaging each value with its neighbors
+            mask = box_blur(&mask, width, height, self.feather);
+        }
+
+        ImageBuffer::from_fn(width, height, |x, y| {
+            let weight = mask[(y * width + x) as usize];
+            let weight = if self.invert { 1.0 - weight } else { weight };
+            // Effects may change the size of the frame
+            let original = *img.get_pixel(x * img.width() / width, y * img.height() / height);
+            let mixed = self.mode.mix(original, *output.get_pixel(x, y), self.opacity.clamp(0.0, 1.0));
+            // The soft edges of the region mix like light, whatever the blend mode
+            blend_weighted(original, mixed, weight)
+        })
+    }
+
+    fn step(&self, rng: &mut GlitchRng) -> Box<dyn Effect> {
+        Box::new(Layer {
+            effect: self.effect.step(rng),
+            shape: self.shape.clone(),
+            ..*self
+        })
+    }
+}
@@ -2,229 +2,35 @@ extern crate image;
 extern crate rand;
 extern crate noise;
 extern crate clap;
-
-use image::{GenericImage, ImageBuffer, Rgba, Pixel};
-use rand::distributions::{Normal, Range, IndependentSample};
+extern crate gif;
+extern crate image_webp;
+extern crate png;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate toml;
+
+mod effects;
+mod keyframes;
+mod layer;
+mod mask;
+mod options;
+mod output;
+mod pipeline;
+mod presets;
+mod recipe;
+mod util;
+
+use image::GenericImageView;
 use rand::Rng;
-use std::borrow::Borrow;
+use std::fs;
+use std::path::Path;
 use std::str::FromStr;
-use noise::*;
 use clap::{App, Arg, SubCommand, ArgMatches, Shell};
-
-type RgbaBuf = ImageBuffer<Rgba<u8>, Vec<<Rgba<u8> as Pixel>::Subpixel>>;
-
-// Poor man's typedef
-trait RgbaImage: GenericImage<Pixel=Rgba<u8>> {}
-
-impl<T> RgbaImage for T where T: GenericImage<Pixel=Rgba<u8>> {}
-
-#[derive(Clone)]
-struct Options {
-    // shift: ShiftOptions,
-    shift: LimitedShiftOptions,
-    scan: ScanlineOptions,
-    wind: WindOptions,
-    blocks: BlockShiftOptions,
-}
-
-impl Options {
-    fn new(shift: LimitedShiftOptions, scan: ScanlineOptions, wind: WindOptions, blocks: BlockShiftOptions) -> Options {
-        Options { shift, scan, wind, blocks }
-    }
-
-    fn step(&self) -> Options {
-        Options {
-            shift: self.shift,
-            scan: self.scan.step(),
-            wind: self.wind,
-            blocks: self.blocks.step(),
-        }
-    }
-}
-
-/// Options for color channel offsetting
-#[derive(Copy, Clone)]
-struct ShiftOptions {
-    r: ChannelShiftOptions,
-    g: ChannelShiftOptions,
-    b: ChannelShiftOptions,
-}
-
-#[derive(Copy, Clone)]
-struct ChannelShiftOptions {
-    base_shift_x: f64,
-    base_shift_y: f64,
-    current_shift_x: f64,
-    current_shift_y: f64,
-    radius: f64,
-    max_move: f64,
-}
-
-impl ShiftOptions {
-    fn random(offset: f64, radius: f64, max_move: f64) -> ShiftOptions {
-        ShiftOptions {
-            r: ChannelShiftOptions::random(offset, radius, max_move),
-            g: ChannelShiftOptions::random(offset, radius, max_move),
-            b: ChannelShiftOptions::random(offset, radius, max_move),
-        }
-    }
-
-    fn step(&self) -> ShiftOptions {
-        ShiftOptions {
-            r: self.r.step(),
-            g: self.g.step(),
-            b: self.b.step(),
-        }
-    }
-}
-
-impl ChannelShiftOptions {
-    fn random(offset: f64, radius: f64, max_move: f64) -> ChannelShiftOptions {
-        // let distribution = Normal::new(0, offset/3.0);
-
-        let distribution = Range::new(0.0, 2.0 * std::f64::consts::PI);
-        let angle = distribution.ind_sample(&mut rand::thread_rng());
-        let base_shift_x = f64::cos(angle) * offset;
-        let base_shift_y = f64::sin(angle) * offset;
-
-        ChannelShiftOptions {
-            base_shift_x,
-            base_shift_y,
-            current_shift_x: base_shift_x,
-            current_shift_y: base_shift_y,
-            radius,
-            max_move,
-        }
-    }
-
-    fn step(&self) -> ChannelShiftOptions {
-        // Chosen so ~99.7% of values will lie within radius
-        let distribution = Normal::new(0.0, self.radius / 3.0f64);
-        let new_x = self.base_shift_x + distribution.ind_sample(&mut rand::thread_rng());
-        let new_y = self.base_shift_y + distribution.ind_sample(&mut rand::thread_rng());
-
-        let mut dx = new_x - self.current_shift_x;
-        let mut dy = new_y - self.current_shift_y;
-        let dist_sq = dx * dx + dy * dy;
-        if dist_sq > self.max_move * self.max_move {
-            let scale = self.max_move / f64::sqrt(dist_sq);
-            dx *= scale;
-            dy *= scale;
-        }
-
-        ChannelShiftOptions {
-            current_shift_x: self.current_shift_x + dx,
-            current_shift_y: self.current_shift_y + dy,
-            ..*self
-        }
-    }
-}
-
-/// Options for the red/cyan color offsetting
-#[derive(Copy, Clone)]
-struct LimitedShiftOptions {
-    distance: i32
-}
-
-impl LimitedShiftOptions {
-    fn new(distance: i32) -> LimitedShiftOptions {
-        LimitedShiftOptions {
-            distance
-        }
-    }
-}
-
-/// Options for the scanline and sinuoid desync effect
-#[derive(Copy, Clone)]
-struct ScanlineOptions {
-    vert_dist: u32,
-    gap_size: u32,
-    desync_phase_shift: f64,
-    desync_amplitude: f64,
-    desync_frequency: f64,
-}
-
-impl ScanlineOptions {
-    fn random(vert_dist: u32, gap_size: u32, desync_amplitude: f64, desync_frequency: f64) -> ScanlineOptions {
-        let desync_phase_shift = rand::thread_rng().next_f64() * desync_frequency;
-        ScanlineOptions {
-            vert_dist,
-            gap_size,
-            desync_phase_shift,
-            desync_amplitude,
-            desync_frequency,
-        }
-    }
-
-    fn step(&self) -> ScanlineOptions {
-        let desync_phase_shift = self.desync_phase_shift + 0.1 * self.desync_frequency;
-        ScanlineOptions {
-            desync_phase_shift,
-            ..*self
-        }
-    }
-}
-
-/// Options for the block movement effect
-#[derive(Clone)]
-struct BlockShiftOptions {
-    blocks: Vec<Block>
-}
-
-#[derive(Copy, Clone)]
-struct Block {
-    min_y: u32,
-    height: u32,
-    shift: i32,
-}
-
-impl BlockShiftOptions {
-    fn random(num_blocks: usize, max_y: u32) -> BlockShiftOptions {
-        let mut rng = rand::thread_rng();
-        let mut start_lines = vec!();
-        for i in 0..num_blocks {
-            start_lines.push(rng.gen_range(0, max_y));
-        }
-        start_lines.sort();
-        let mut blocks = vec!();
-        for i in 0..num_blocks {
-            let min_y = start_lines[i];
-            let max_end = if i == num_blocks - 1 { max_y } else { start_lines[i + 1] };
-            let height = rng.gen_range(0, max_end - min_y);
-            let shift = rng.gen_range(-20, 20);
-            blocks.push(Block {
-                min_y,
-                height: u32::max(16, height),
-                shift: if shift < 0 && shift >= -3 { -3 } else if shift >= 0 && shift <= 3 { 3 } else { shift },
-            })
-        }
-        BlockShiftOptions {
-            blocks
-        }
-    }
-
-    fn step(&self) -> BlockShiftOptions {
-        return BlockShiftOptions {
-            blocks: self.blocks.iter().map(|&Block { min_y, height, shift }| Block { min_y: min_y + 3, height, shift }).collect::<Vec<_>>()
-        };
-    }
-}
-
-/// Options for the wind (horizontal line stretching) effect
-#[derive(Copy, Clone)]
-struct WindOptions {
-    wind_onset_chance: f32,
-    wind_stop_chance: f32,
-}
-
-impl WindOptions {
-    fn new(wind_onset_chance: f32, wind_stop_chance: f32) -> WindOptions {
-        WindOptions {
-            wind_onset_chance,
-            wind_stop_chance,
-        }
-    }
-}
+use effects::{Effect, Params, RenderInfo};
+use options::Options;
+use util::{GlitchRng, RgbaBuf};
 
 fn cli<'a, 'b>() -> App<'a, 'b> {
     App::new("Glitch")
@@ -247,70 +53,75 @@ fn cli<'a, 'b>() -> App<'a, 'b> {
                 .default_value("1")
                 .help("Number of images to generate. If generating multiple images, they will form a continuous animation")
                 .display_order(1))
-            .arg(Arg::with_name("color shift amount")
-                .long("color-shift")
+            .arg(Arg::with_name("loop")
+                .long("loop")
+                .help("Make the animation loop seamlessly, so the frame after the last one would equal the first")
+                .display_order(1))
+            .arg(Arg::with_name("format")
+                .long("format")
                 .takes_value(true)
-                .value_name("N")
-                .validator(|n| validate::<i32>(n, "Expected an integer"))
-                .default_value("4")
-                .help("Amount of offset from original position of each color channel")
-                .display_order(2))
-            .arg(Arg::with_name("scanline height")
-                .long("scan-height")
+                .value_name("FORMAT")
+                .possible_values(&["png", "gif", "apng", "webp"])
+                .help("Output format. 'png' writes one image per frame, 'gif', 'apng' and 'webp' a single animation. Chosen from the output file extension if not given")
+                .display_order(1))
+            .arg(Arg::with_name("output")
+                .short("o")
+                .long("output")
                 .takes_value(true)
-                .value_name("N")
-                .validator(|n| validate::<u32>(n, "Expected an integer"))
-                .default_value("6")
-                .help("Height of each scanline")
-                .display_order(3))
-            .arg(Arg::with_name("scanline gap height")
-                .long("scan-gap")
+                .value_name("TEMPLATE")
+                .validator(|t| output::PathTemplate::parse(&t).map(|_| ()))
+                .help("Output path. May contain {stem}, {frame}, {frame:04}, {seed}, {preset} and {ext}, e.g. 'out/{stem}_{frame:04}.png'")
+                .display_order(1))
+            .arg(Arg::with_name("frame delay")
+                .long("frame-delay")
                 .takes_value(true)
-                .value_name("M")
+                .value_name("MS")
                 .validator(|n| validate::<u32>(n, "Expected an integer"))
-                .default_value("3")
-                .help("Height of the gap between scanlines")
-                .display_order(4))
-            .arg(Arg::with_name("desync amplitude")
-                .long("desync-amp")
+                .default_value("100")
+                .help("Display time of each frame of an animation in milliseconds")
+                .display_order(1))
+            .arg(Arg::with_name("repeat")
+                .long("repeat")
                 .takes_value(true)
                 .value_name("N")
-                .validator(|n| validate::<f64>(n, "Expected a float"))
-                .default_value("6.0")
-                .help("Amplitude for the desync effect")
-                .display_order(5))
-            .arg(Arg::with_name("desync frequency")
-                .long("desync-freq")
-                .takes_value(true)
-                .value_name("M")
-                .validator(|n| validate::<f64>(n, "Expected a float"))
-                .default_value("0.3")
-                .help("Frequency for the desync effect")
-                .display_order(6))
-            .arg(Arg::with_name("wind onset chance")
-                .long("wind-onset")
+                .validator(|n| if n == "forever" { Ok(()) } else { validate::<u16>(n, "Expected an integer or 'forever'") })
+                .default_value("forever")
+                .help("Number of times an animation repeats after playing once")
+                .display_order(1))
+            .arg(Arg::with_name("gif speed")
+                .long("gif-speed")
                 .takes_value(true)
                 .value_name("N")
-                .validator(|n| validate::<f32>(n, "Expected a float"))
-                .default_value("0.05")
-                .help("Onset chance for wind effect")
-                .display_order(7))
-            .arg(Arg::with_name("wind continue chance")
-                .long("wind-continue")
-                .takes_value(true)
-                .value_name("M")
-                .validator(|n| validate::<f64>(n, "Expected a float"))
-                .default_value("0.15")
-                .help("Continue chance for wind effect")
-                .display_order(8))
-            .arg(Arg::with_name("block count")
-                .long("blocks")
+                .validator(|n| match n.parse::<i32>() {
+                    Ok(speed) if (1..=30).contains(&speed) => Ok(()),
+                    _ => Err(String::from("Expected an integer between 1 and 30")),
+                })
+                .default_value("10")
+                .help("Speed of the GIF color quantization, from 1 (best quality) to 30 (fastest)")
+                .display_order(1))
+            .args(&option_args())
+            .arg(Arg::with_name("seed")
+                .long("seed")
                 .takes_value(true)
-                .value_name("M")
-                .validator(|n| validate::<usize>(n, "Expected an integer"))
-                .default_value("5")
-                .help("Number of blocks to shift")
-                .display_order(9)))
+                .value_name("N")
+                .validator(|n| validate::<u64>(n, "Expected an integer"))
+                .help("Seed for the random number generator. Rendering the same input with the same seed produces identical images")
+                .display_order(15)))
+        .subcommand(SubCommand::with_name("recipe")
+            .about("Manage recipe files")
+            .subcommand(SubCommand::with_name("dump")
+                .about("Write the given options as a recipe")
+                .args(&option_args())
+                .arg(Arg::with_name("output")
+                    .value_name("FILE")
+                    .help("Output file. The format is chosen from the extension (.toml or .json). Prints TOML to stdout if not given")
+                    .index(1))))
+        .subcommand(SubCommand::with_name("presets")
+            .about("Manage built-in presets")
+            .subcommand(SubCommand::with_name("list")
+                .about("List built-in presets and their options")))
+        .subcommand(SubCommand::with_name("effects")
+            .about("List available effects"))
         .subcommand(SubCommand::with_name("completion")
             .about("Generate completion scripts")
             .arg(Arg::with_name("zsh")
@@ -331,12 +142,252 @@ fn cli<'a, 'b>() -> App<'a, 'b> {
                 .display_order(1)))
 }
 
+/// Options shared between rendering and recipe generation
+fn option_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("color shift amount")
+            .long("color-shift")
+            .takes_value(true)
+            .value_name("N")
+            .validator(|n| validate::<i32>(n, "Expected an integer"))
+            .default_value("4")
+            .help("Amount of offset from original position of each color channel")
+            .display_order(2),
+        Arg::with_name("shift mode")
+            .long("shift-mode")
+            .takes_value(true)
+            .value_name("MODE")
+            .possible_values(&["red-cyan", "rgb"])
+            .help("Color shift mode. 'red-cyan' offsets the red against the green/blue channels horizontally, 'rgb' moves each channel independently in 2D")
+            .display_order(2),
+        Arg::with_name("shift offset")
+            .long("shift-offset")
+            .takes_value(true)
+            .value_name("N|R,G,B")
+            .validator(|n| validate_channels(&n))
+            .help("Distance of the channels from their original position in rgb shift mode, shared or per channel")
+            .display_order(2),
+        Arg::with_name("shift radius")
+            .long("shift-radius")
+            .takes_value(true)
+            .value_name("N|R,G,B")
+            .validator(|n| validate_channels(&n))
+            .help("How far channels drift around their offset during an animation in rgb shift mode, shared or per channel")
+            .display_order(2),
+        Arg::with_name("shift max move")
+            .long("shift-max-move")
+            .takes_value(true)
+            .value_name("N|R,G,B")
+            .validator(|n| validate_channels(&n))
+            .help("Maximum distance a channel moves between two frames in rgb shift mode, shared or per channel")
+            .display_order(2),
+        Arg::with_name("scanline height")
+            .long("scan-height")
+            .takes_value(true)
+            .value_name("N")
+            .validator(|n| validate::<u32>(n, "Expected an integer"))
+            .default_value("6")
+            .help("Height of each scanline")
+            .display_order(3),
+        Arg::with_name("scanline gap height")
+            .long("scan-gap")
+            .takes_value(true)
+            .value_name("M")
+            .validator(|n| validate::<u32>(n, "Expected an integer"))
+            .default_value("3")
+            .help("Height of the gap between scanlines")
+            .display_order(4),
+        Arg::with_name("desync amplitude")
+            .long("desync-amp")
+            .takes_value(true)
+            .value_name("N")
+            .validator(|n| validate::<f64>(n, "Expected a float"))
+            .default_value("6.0")
+            .help("Amplitude for the desync effect")
+            .display_order(5),
+        Arg::with_name("desync frequency")
+            .long("desync-freq")
+            .takes_value(true)
+            .value_name("M")
+            .validator(|n| validate::<f64>(n, "Expected a float"))
+            .default_value("0.3")
+            .help("Frequency for the desync effect")
+            .display_order(6),
+        Arg::with_name("wind onset chance")
+            .long("wind-onset")
+            .takes_value(true)
+            .value_name("N")
+            .validator(|n| validate::<f32>(n, "Expected a float"))
+            .default_value("0.05")
+            .help("Onset chance for wind effect")
+            .display_order(7),
+        Arg::with_name("wind continue chance")
+            .long("wind-continue")
+            .takes_value(true)
+            .value_name("M")
+            .validator(|n| validate::<f64>(n, "Expected a float"))
+            .default_value("0.15")
+            .help("Continue chance for wind effect")
+            .display_order(8),
+        Arg::with_name("wind direction")
+            .long("wind-direction")
+            .takes_value(true)
+            .value_name("DIR")
+            .validator(|n| validate::<effects::WindDirection>(n, "Expected left, right, up, down or an angle in degrees"))
+            .default_value("left")
+            .help("Direction the wind streaks extend in: left, right, up, down or an angle in degrees counterclockwise from right")
+            .display_order(8),
+        Arg::with_name("wind fade")
+            .long("wind-fade")
+            .takes_value(true)
+            .value_name("N")
+            .validator(|n| validate::<f64>(n, "Expected a number"))
+            .default_value("0")
+            .help("Length in pixels over which wind streaks fade out. 0 disables fading")
+            .display_order(8),
+        Arg::with_name("wind fade mode")
+            .long("wind-fade-mode")
+            .takes_value(true)
+            .value_name("MODE")
+            .possible_values(&["alpha", "blend"])
+            .default_value("blend")
+            .help("Let fading wind streaks become transparent or blend with the pixels they cover")
+            .display_order(8),
+        Arg::with_name("block count")
+            .long("blocks")
+            .takes_value(true)
+            .value_name("M")
+            .validator(|n| validate::<usize>(n, "Expected an integer"))
+            .default_value("5")
+            .help("Number of blocks to shift")
+            .display_order(9),
+        Arg::with_name("grain alpha")
+            .long("grain-alpha")
+            .takes_value(true)
+            .value_name("N")
+            .validator(|n| validate::<f64>(n, "Expected a number"))
+            .default_value("0.2")
+            .help("Strength of the film grain")
+            .display_order(9),
+        Arg::with_name("grain frequency")
+            .long("grain-frequency")
+            .takes_value(true)
+            .value_name("N")
+            .validator(|n| validate::<f64>(n, "Expected a number"))
+            .default_value("96")
+            .help("Frequency of the film grain. Higher values result in finer grain")
+            .display_order(9),
+        Arg::with_name("grain octaves")
+            .long("grain-octaves")
+            .takes_value(true)
+            .value_name("N")
+            .validator(|n| validate::<usize>(n, "Expected an integer"))
+            .default_value("6")
+            .help("Number of noise layers of the film grain")
+            .display_order(9),
+        Arg::with_name("grain persistence")
+            .long("grain-persistence")
+            .takes_value(true)
+            .value_name("N")
+            .validator(|n| validate::<f64>(n, "Expected a number"))
+            .default_value("0.5")
+            .help("Strength of each noise layer of the film grain relative to the previous one")
+            .display_order(9),
+        Arg::with_name("grain lacunarity")
+            .long("grain-lacunarity")
+            .takes_value(true)
+            .value_name("N")
+            .validator(|n| validate::<f64>(n, "Expected a number"))
+            .default_value("2")
+            .help("Frequency of each noise layer of the film grain relative to the previous one")
+            .display_order(9),
+        Arg::with_name("grain color")
+            .long("grain-color")
+            .takes_value(true)
+            .value_name("MODE")
+            .possible_values(&["mono", "rgb"])
+            .default_value("mono")
+            .help("Use the same grain for all color channels or separate grain per channel")
+            .display_order(9),
+        Arg::with_name("grain blend")
+            .long("grain-blend")
+            .takes_value(true)
+            .value_name("MODE")
+            .possible_values(&["add", "overlay"])
+            .default_value("add")
+            .help("Brighten the image by the grain or overlay it, darkening dark and brightening light areas")
+            .display_order(9),
+        Arg::with_name("grain motion")
+            .long("grain-motion")
+            .takes_value(true)
+            .value_name("MODE")
+            .possible_values(&["reseed", "coherent"])
+            .default_value("reseed")
+            .help("Fresh grain every frame of an animation, or grain flowing smoothly between frames")
+            .display_order(9),
+        Arg::with_name("grain speed")
+            .long("grain-speed")
+            .takes_value(true)
+            .value_name("N")
+            .validator(|n| validate::<f64>(n, "Expected a number"))
+            .default_value("0.05")
+            .help("Distance the grain moves through the noise each frame with coherent motion")
+            .display_order(9),
+        Arg::with_name("preset")
+            .long("preset")
+            .takes_value(true)
+            .value_name("NAME")
+            .possible_values(&presets::PRESETS.iter().map(|preset| preset.name).collect::<Vec<_>>())
+            .conflicts_with("recipe")
+            .help("Start from a built-in set of options. Options given on the command line take precedence")
+            .display_order(10),
+        Arg::with_name("recipe")
+            .long("recipe")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Load options and pipeline from a TOML or JSON recipe file. Options given on the command line take precedence")
+            .display_order(11),
+        Arg::with_name("pipeline")
+            .long("pipeline")
+            .takes_value(true)
+            .value_name("SPEC")
+            .validator(|spec| pipeline::parse(&spec).map(|_| ()))
+            .default_value(effects::DEFAULT_PIPELINE)
+            .help("Comma-separated list of effects to apply, in order. Effects may be repeated and take parameters, e.g. 'wind(onset=0.2,continue=0.5)'")
+            .display_order(12),
+        Arg::with_name("pipeline file")
+            .long("pipeline-file")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Read the pipeline spec from a file, one effect per line. Takes precedence over --pipeline")
+            .display_order(13),
+        Arg::with_name("keyframes")
+            .long("keyframe")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("SPEC")
+            .validator(|spec| keyframes::parse(&spec).map(|_| ()))
+            .help("Animate an effect parameter across frames, e.g. 'color-shift.distance=0@0,12@30'. Append ':ease' or ':step' to the parameter to change the interpolation. Can be given multiple times")
+            .display_order(14),
+    ]
+}
+
 fn main() {
     let matches = cli().get_matches();
 
     match matches.subcommand() {
         ("completion", Some(m)) => gen_completions(m),
         ("render", Some(m)) => render(m),
+        ("recipe", Some(m)) => match m.subcommand() {
+            ("dump", Some(m)) => dump_recipe(m),
+            _ => exit_with_error(m.usage()),
+        },
+        ("presets", Some(m)) => match m.subcommand() {
+            ("list", Some(_)) => list_presets(),
+            _ => exit_with_error(m.usage()),
+        },
+        ("effects", Some(_)) => list_effects(),
         _ => {
             cli().print_help().expect("Unable to print help");
             std::process::exit(1);
@@ -360,302 +411,288 @@ fn gen_completions(matches: &ArgMatches) {
     }
 }
 
-fn validate<T: FromStr>(input: String, error: &str) -> Result<(), String> {
-    if let Ok(val) = input.parse::<T>() {
-        Ok(())
-    } else {
-        Err(String::from(error))
+fn list_effects() {
+    for effect in effects::EFFECTS {
+        println!("{:<12} {}", effect.name, effect.description);
     }
 }
 
-fn unwrap_opt<T: FromStr>(matches: &ArgMatches, name: &str) -> T {
-    matches.value_of(name).and_then(|n| n.parse::<T>().ok()).unwrap()
-}
-
-fn render(matches: &ArgMatches) {
-    let img = image::open(matches.value_of("file").unwrap());
-    let img = img.expect("Unable to load input image");
-    println!("Size: {:?}", img.dimensions());
-    println!("Color model: {:?}", img.color());
-    println!("----------------------");
-
-    let color_shift = unwrap_opt(matches, "color shift amount");
-    let lim_shift_options = LimitedShiftOptions::new(color_shift);
-
-    let scanline_vert_dist = unwrap_opt(matches, "scanline height");
-    let scanline_gap_dist = unwrap_opt(matches, "scanline gap height");
-    let desync_amplitude = unwrap_opt(matches, "desync amplitude");
-    let desync_frequency = unwrap_opt(matches, "desync frequency");
-    let scanline_options = ScanlineOptions::random(
-        scanline_vert_dist,
-        scanline_gap_dist,
-        desync_amplitude,
-        desync_frequency
-    );
-
-    let wind_onset_chance = unwrap_opt(matches, "wind onset chance");
-    let wind_continue_chance = unwrap_opt(matches, "wind continue chance");
-    let wind_options = WindOptions::new(wind_onset_chance, wind_continue_chance);
-
-    let block_count = unwrap_opt(matches, "block count");
-    let block_options = BlockShiftOptions::random(block_count, img.height());
-
-    let mut opts = Options::new(
-        lim_shift_options,
-        scanline_options,
-        wind_options,
-        block_options
-    );
-
-    let n = unwrap_opt(matches, "number");
-    for i in 0..n {
+fn list_presets() {
+    for preset in presets::PRESETS {
+        println!("{} - {}", preset.name, preset.description);
+        let recipe = recipe::dump(&(preset.options)(), recipe::Format::Toml).unwrap_or_else(|e| exit_with_error(&e));
+        for line in recipe.lines() {
+            if line.is_empty() {
+                println!();
+            } else {
+                println!("    {}", line);
+            }
+        }
         println!();
-        println!("Pass {}", i + 1);
-        let derived_img = glitch_img(&img, &opts);
-        opts = opts.step();
-        derived_img.save(format!("glitch_{}.png", i)).expect("Unable to save output image");
     }
 }
 
-fn clamping_add(val: u32, offset: i32, max: u32) -> u32 {
-    if -offset > val as i32 {
-        return 0;
-    }
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    std::process::exit(1);
+}
 
-    let result = (offset + val as i32) as u32;
-    if result > max {
-        max
+fn validate<T: FromStr>(input: String, error: &str) -> Result<(), String> {
+    if input.parse::<T>().is_ok() {
+        Ok(())
     } else {
-        result
+        Err(String::from(error))
     }
 }
 
-fn blend<T>(pixels: &[T]) -> Rgba<u8> where T: Borrow<Rgba<u8>> {
-    let alpha_values = pixels.iter().map(|px| px.borrow().channels()[3]);
-    let max_alpha = alpha_values.max().expect("Needs at least one pixel to blend");
-
-    if max_alpha == 0 {
-        return Rgba::from_channels(0, 0, 0, 0);
+/// Validate a single number or three comma-separated numbers for the red, green and blue channel
+fn validate_channels(input: &str) -> Result<(), String> {
+    let values = input.split(',').collect::<Vec<_>>();
+    if (values.len() == 1 || values.len() == 3) && values.iter().all(|v| v.trim().parse::<f64>().is_ok()) {
+        Ok(())
+    } else {
+        Err(String::from("Expected a number or three comma-separated numbers"))
     }
+}
 
-    let max_alpha = (max_alpha as f64) / (u8::max_value() as f64);
-
-    // To approximate light mixing, we set
-    // final_rgb[color] = max_{k < n} rgb_k[color] * alpha[k] / (max alpha)
-
-    let mut final_r = 0;
-    let mut final_g = 0;
-    let mut final_b = 0;
-    let mut final_a = 0.0;
-    for p in pixels {
-        let (r, g, b, a) = p.borrow().channels4();
-
-        let a = (a as f64) / (u8::max_value() as f64);
-        let r = (r as f64) * a / max_alpha;
-        let g = (g as f64) * a / max_alpha;
-        let b = (b as f64) * a / max_alpha;
-        final_r = u8::max(final_r, r as u8);
-        final_g = u8::max(final_g, g as u8);
-        final_b = u8::max(final_b, b as u8);
-
-        final_a += (1.0 - final_a) * a;
+/// Set a parameter of the rgb shift either for all channels or, given `R,G,B`, per channel
+fn set_channel_param(opts: &mut Options, matches: &ArgMatches, name: &str, param: &str) {
+    if let Some(value) = matches.value_of(name) {
+        let values = value.split(',').map(str::trim).collect::<Vec<_>>();
+        let result = if values.len() == 3 {
+            ["red", "green", "blue"].iter().zip(values).try_for_each(|(channel, value)| {
+                opts.rgb_shift.set_param(&format!("{}-{}", channel, param), value)
+            })
+        } else {
+            opts.rgb_shift.set_param(param, value)
+        };
+        result.unwrap_or_else(|e| exit_with_error(&e));
     }
-
-    Rgba::from_channels(final_r, final_g, final_b, (final_a * 255.0) as u8)
 }
 
-fn blend_alpha_one_minus_alpha(a: f64, col1: u8, col2: u8) -> u8 {
-    let col1 = col1 as f64;
-    let col2 = col2 as f64;
-
-    f64::min(u8::max_value() as f64, a * col1 + (1.0 - a) * col2) as u8
+fn unwrap_opt<T: FromStr>(matches: &ArgMatches, name: &str) -> T {
+    matches.value_of(name).and_then(|n| n.parse::<T>().ok()).unwrap()
 }
 
-fn glitch_img<T: RgbaImage>(img: &T, config: &Options) -> RgbaBuf {
-    let img = scanlines(img, &config.scan);
-    let img = offset_red_cyan(&img, &config.shift);
-    //let img = noise(&img);
-    let img = wind(&img, &config.wind);
-    let img = offset_blocks(&img, &config.blocks);
-    img
+fn explicit_opt<T: FromStr>(matches: &ArgMatches, name: &str) -> Option<T> {
+    if matches.occurrences_of(name) > 0 {
+        Some(unwrap_opt(matches, name))
+    } else {
+        None
+    }
 }
 
-fn scanlines<T: RgbaImage>(img: &T, config: &ScanlineOptions) -> RgbaBuf {
-    println!("* Adding scanlines");
-
-    ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
-        // Insert scanline gap
-        if y % (config.vert_dist + config.gap_size) >= config.vert_dist {
-            let base_y = y - (y % (config.vert_dist + config.gap_size));
-            let prev_line = base_y + config.vert_dist - 1;
-            let next_line = prev_line + config.gap_size;
-
-            let mut blend_sources = vec![];
-            for &(index, weight) in [(-1, 8), (0, 4), (1, 8)].iter() {
-                let src_x = clamping_add(x, index, img.width() - 1);
-                let (r, g, b, a) = img.get_pixel(src_x, prev_line).channels4();
-                blend_sources.push(Rgba::from_channels(r, g, b, a / weight));
-
-                if next_line < img.height() {
-                    let (r, g, b, a) = img.get_pixel(src_x, next_line).channels4();
-                    blend_sources.push(Rgba::from_channels(r, g, b, a / weight));
-                }
-            }
-
-            return blend(&blend_sources[..]);
-        }
-
-        // Desync lines
-        let line = y / (config.vert_dist + config.gap_size);
-        let line = line as f64 + config.desync_phase_shift;
-
-        let desync_x_shift = f64::sin(line / config.desync_frequency) * config.desync_amplitude;
-        let desync_x_shift = desync_x_shift + 0.3 * config.desync_amplitude * (rand::thread_rng().next_f64() - 0.5);
-        let x = clamping_add(x, desync_x_shift as i32, img.width() - 1);
-        img.get_pixel(x, y)
-    })
-}
+/// Assemble the render options from the recipe or preset, if any, and the explicitly given flags
+fn options_from_matches(matches: &ArgMatches) -> Options {
+    let mut opts = if let Some(path) = matches.value_of("recipe") {
+        recipe::load(path).unwrap_or_else(|e| exit_with_error(&e))
+    } else if let Some(name) = matches.value_of("preset") {
+        (presets::lookup(name).expect("Unknown preset").options)()
+    } else {
+        Options::default()
+    };
 
-fn offset_red_cyan<T: RgbaImage>(img: &T, config: &LimitedShiftOptions) -> RgbaBuf {
-    println!("* Offsetting color channels");
+    if let Some(distance) = explicit_opt::<i32>(matches, "color shift amount") {
+        opts.shift.distance = distance;
+        // Also the offset of the channels when shifting them independently
+        opts.rgb_shift.set_param("offset", &distance.to_string()).unwrap_or_else(|e| exit_with_error(&e));
+    }
 
-    ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
-        let red_x = clamping_add(x, config.distance, img.width() - 1);
-        let (r, _, _, a) = img.get_pixel(red_x, y).channels4();
-        let red_px = Rgba::from_channels(r, 0, 0, a);
+    if let Some(vert_dist) = explicit_opt(matches, "scanline height") {
+        opts.scan.vert_dist = vert_dist;
+    }
+    if let Some(gap_size) = explicit_opt(matches, "scanline gap height") {
+        opts.scan.gap_size = gap_size;
+    }
+    if let Some(amplitude) = explicit_opt(matches, "desync amplitude") {
+        opts.scan.desync_amplitude = amplitude;
+    }
+    if let Some(frequency) = explicit_opt(matches, "desync frequency") {
+        opts.scan.desync_frequency = frequency;
+    }
 
-        let cyan_x = clamping_add(x, -config.distance, img.width() - 1);
-        let (_, g, b, a) = img.get_pixel(cyan_x, y).channels4();
-        let cyan_px = Rgba::from_channels(0, g, b, a);
+    if let Some(onset_chance) = explicit_opt(matches, "wind onset chance") {
+        opts.wind.wind_onset_chance = onset_chance;
+    }
+    if let Some(continue_chance) = explicit_opt(matches, "wind continue chance") {
+        opts.wind.wind_stop_chance = continue_chance;
+    }
+    if let Some(direction) = explicit_opt(matches, "wind direction") {
+        opts.wind.direction = direction;
+    }
+    if let Some(fade) = explicit_opt(matches, "wind fade") {
+        opts.wind.fade = fade;
+    }
+    if let Some(mode) = explicit_opt(matches, "wind fade mode") {
+        opts.wind.fade_mode = mode;
+    }
 
-        blend(&[red_px, cyan_px])
-    })
-}
+    if let Some(count) = explicit_opt(matches, "block count") {
+        opts.blocks.count = count;
+    }
 
-fn offset_channels<T: RgbaImage>(img: &T, config: &ShiftOptions) -> RgbaBuf {
-    println!("* Offsetting color channels");
+    if let Some(alpha) = explicit_opt(matches, "grain alpha") {
+        opts.grain.alpha = alpha;
+    }
+    if let Some(frequency) = explicit_opt(matches, "grain frequency") {
+        opts.grain.frequency = frequency;
+    }
+    if let Some(octaves) = explicit_opt(matches, "grain octaves") {
+        opts.grain.octaves = octaves;
+    }
+    if let Some(persistence) = explicit_opt(matches, "grain persistence") {
+        opts.grain.persistence = persistence;
+    }
+    if let Some(lacunarity) = explicit_opt(matches, "grain lacunarity") {
+        opts.grain.lacunarity = lacunarity;
+    }
+    if let Some(color) = explicit_opt(matches, "grain color") {
+        opts.grain.color = color;
+    }
+    if let Some(blend) = explicit_opt(matches, "grain blend") {
+        opts.grain.blend = blend;
+    }
+    if let Some(motion) = explicit_opt(matches, "grain motion") {
+        opts.grain.motion = motion;
+    }
+    if let Some(speed) = explicit_opt(matches, "grain speed") {
+        opts.grain.speed = speed;
+    }
 
-    let r_channel = ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
-        let x = clamping_add(x, config.r.current_shift_x as i32, img.width() - 1);
-        let y = clamping_add(y, config.r.current_shift_y as i32, img.height() - 1);
+    set_channel_param(&mut opts, matches, "shift offset", "offset");
+    set_channel_param(&mut opts, matches, "shift radius", "radius");
+    set_channel_param(&mut opts, matches, "shift max move", "max-move");
 
-        let (r, _, _, a) = img.get_pixel(x, y).channels4();
-        Rgba::from_channels(r, 0, 0, a)
-    });
+    let stages = match matches.value_of("pipeline file") {
+        Some(path) => {
+            let spec = fs::read_to_string(path).expect("Unable to read pipeline file");
+            Some(pipeline::parse(&spec))
+        },
+        None => explicit_opt::<String>(matches, "pipeline").map(|spec| pipeline::parse(&spec)),
+    };
+    if let Some(stages) = stages {
+        opts.pipeline = stages.unwrap_or_else(|e| exit_with_error(&e));
+    }
 
-    let g_channel = ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
-        let x = clamping_add(x, config.g.current_shift_x as i32, img.width() - 1);
-        let y = clamping_add(y, config.g.current_shift_y as i32, img.height() - 1);
+    if let Some(specs) = matches.values_of("keyframes") {
+        opts.keyframes = specs.map(|spec| keyframes::parse(spec).unwrap_or_else(|e| exit_with_error(&e))).collect();
+    }
 
-        let (_, g, _, a) = img.get_pixel(x, y).channels4();
-        Rgba::from_channels(0, g, 0, a)
-    });
+    match matches.value_of("shift mode") {
+        Some("rgb") => opts.use_rgb_shift(),
+        Some("red-cyan") => opts.use_red_cyan_shift().unwrap_or_else(|e| exit_with_error(&e)),
+        _ => {},
+    }
 
-    let b_channel = ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
-        let x = clamping_add(x, config.b.current_shift_x as i32, img.width() - 1);
-        let y = clamping_add(y, config.b.current_shift_y as i32, img.height() - 1);
+    opts
+}
 
-        let (_, _, b, a) = img.get_pixel(x, y).channels4();
-        Rgba::from_channels(0, 0, b, a)
-    });
+fn dump_recipe(matches: &ArgMatches) {
+    let opts = options_from_matches(matches);
 
-    ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
-        let p1 = r_channel.get_pixel(x, y);
-        let p2 = g_channel.get_pixel(x, y);
-        let p3 = b_channel.get_pixel(x, y);
-        blend(&[p1, p2, p3])
-    })
+    match matches.value_of("output") {
+        Some(path) => {
+            let recipe = recipe::dump(&opts, recipe::Format::from_path(path)).unwrap_or_else(|e| exit_with_error(&e));
+            fs::write(path, recipe).expect("Unable to write recipe");
+        },
+        None => {
+            let recipe = recipe::dump(&opts, recipe::Format::Toml).unwrap_or_else(|e| exit_with_error(&e));
+            print!("{}", recipe);
+        },
+    }
 }
 
-fn noise<T: RgbaImage>(img: &T) -> RgbaBuf {
-    println!("* Adding grain");
+fn render(matches: &ArgMatches) {
+    let img = image::open(matches.value_of("file").unwrap());
+    let img = img.expect("Unable to load input image");
+    println!("Size: {:?}", img.dimensions());
+    println!("Color model: {:?}", img.color());
 
-    let noise = noise::Fbm::new()
-        .set_frequency(96.0)
-        .set_lacunarity(2.0)
-        .set_octaves(6)
-        .set_persistence(0.5)
-        .set_seed(rand::thread_rng().next_u32() as usize);
+    let seed = match matches.value_of("seed") {
+        Some(_) => unwrap_opt(matches, "seed"),
+        None => rand::thread_rng().next_u64(),
+    };
+    println!("Seed: {}", seed);
+    println!("----------------------");
 
-    let max = 0.0;
-    ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
-        // Normalize coordinates to [0,1]
-        let pt = [x as f64 / img.width() as f64, y as f64 / img.height() as f64];
-        let val = noise.get(pt);
+    let opts = options_from_matches(matches);
 
-        // Apply sigmoid shaping
-        let val = val * 4.0; // Sharper edge
-        let val = val - 1.25; // Stay in black area longer
-        let exp = f64::exp(val);
-        let val = exp / (1.0 + exp);
+    let mut rng = util::seeded_rng(seed);
+    let n = unwrap_opt(matches, "number");
+    let info = RenderInfo {
+        width: img.width(),
+        height: img.height(),
+        period: if matches.is_present("loop") { Some(n) } else { None },
+    };
 
-        let noise_alpha = 0.2;
-        let val = (val * noise_alpha * (u8::max_value() as f64)) as u8;
+    let stages = keyframes::apply_to_stages(&opts.keyframes, 0, &opts.pipeline);
+    let mut pipeline = pipeline::build(&stages, &opts, &info, &mut rng)
+        .unwrap_or_else(|e| exit_with_error(&e));
 
-        let (r, g, b, a) = img.get_pixel(x, y).channels4();
+    let animation = output::AnimationOptions {
+        frame_delay: unwrap_opt(matches, "frame delay"),
+        repeat: match matches.value_of("repeat") {
+            Some("forever") => None,
+            _ => Some(unwrap_opt(matches, "repeat")),
+        },
+        gif_speed: unwrap_opt(matches, "gif speed"),
+    };
+    let format = matches.value_of("format").map(|name| output::Format::from_name(name).expect("Unknown output format"));
+    let template = match matches.value_of("output") {
+        Some(template) => template,
+        None => format.unwrap_or(output::Format::Sequence).default_template(),
+    };
+    let template = output::PathTemplate::parse(template).unwrap_or_else(|e| exit_with_error(&e));
+    let vars = template_vars(matches, format, seed);
+    let format = format.unwrap_or_else(|| output::Format::from_path(&template.expand(&vars, 0)));
+    if let output::Format::Sequence = format {
+        if n > 1 && !template.has_frame() {
+            exit_with_error("The output path needs a {frame} placeholder to write more than one image");
+        }
+    }
+    let mut writer = output::writer(format, template, vars, &animation);
 
-        let (r, g, b, a) = if a == 0 {
-            let val = val as u8;
-            (val, val, val, val)
-        } else {
-            let af = a as f64;
-            /*
-            let r = blend_alpha_one_minus_alpha(noise_alpha, val, r);
-            let g = blend_alpha_one_minus_alpha(noise_alpha, val, g);
-            let b = blend_alpha_one_minus_alpha(noise_alpha, val, b);
-            */
-            /*
-            let r = blend_alpha_one_minus_alpha(af, r, val);
-            let g = blend_alpha_one_minus_alpha(af, g, val);
-            let b = blend_alpha_one_minus_alpha(af, b, val);
-            */
-            let r = clamping_add(r as u32, val as i32, u8::max_value() as u32) as u8;
-            let g = clamping_add(g as u32, val as i32, u8::max_value() as u32) as u8;
-            let b = clamping_add(b as u32, val as i32, u8::max_value() as u32) as u8;
-            let a = (af + (1.0 - af) * noise_alpha) as u8;
-            (r, g, b, a)
-        };
+    let img = img.to_rgba8();
+    for i in 0..n {
+        if i > 0 {
+            keyframes::apply(&opts.keyframes, i, &opts.pipeline, &mut pipeline).unwrap_or_else(|e| exit_with_error(&e));
+            pipeline = pipeline.iter().map(|effect| effect.step(&mut rng)).collect();
+        }
 
-        Rgba::from_channels(r, g, b, a)
-    })
+        println!();
+        println!("Pass {}", i + 1);
+        let derived_img = glitch_img(&img, &pipeline, &mut rng);
+        writer.write_frame(&derived_img).unwrap_or_else(|e| exit_with_error(&e));
+    }
+    writer.finish().unwrap_or_else(|e| exit_with_error(&e));
 }
 
-fn wind<T: RgbaImage>(img: &T, config: &WindOptions) -> RgbaBuf {
-    println!("* Applying wind effect");
-
-    let mut img = ImageBuffer::from_fn(img.width(), img.height(), |x, y| img.get_pixel(x, y));
-    let mut rng = rand::thread_rng();
-    for y in 0..img.height() {
-        let mut x = img.width();
-        while x > 0 {
-            x -= 1;
-            let (r, g, b, a) = img[(x, y)].channels4();
-            if a > 0 && rng.next_f32() < config.wind_onset_chance {
-                while x > 0 {
-                    x -= 1;
-                    img[(x, y)] = Rgba::from_channels(r, g, b, a);
-                    if rng.next_f32() < config.wind_stop_chance {
-                        break;
-                    }
-                }
-            }
-        }
-    }
+/// Values for the output path template. `{ext}` is the extension of the output format if one was
+/// given, otherwise that of the input image.
+fn template_vars(matches: &ArgMatches, format: Option<output::Format>, seed: u64) -> output::TemplateVars {
+    let input = Path::new(matches.value_of("file").unwrap());
+    let preset = match (matches.value_of("preset"), matches.value_of("recipe")) {
+        (Some(preset), _) => String::from(preset),
+        (None, Some(recipe)) => file_stem(Path::new(recipe)),
+        (None, None) => String::from("default"),
+    };
+    let ext = match format {
+        Some(format) => String::from(format.extension()),
+        None => input.extension().map_or(String::from("png"), |ext| ext.to_string_lossy().into_owned()),
+    };
 
-    img
+    output::TemplateVars {
+        stem: file_stem(input),
+        seed,
+        preset,
+        ext,
+    }
 }
 
-fn offset_blocks<T: RgbaImage>(img: &T, config: &BlockShiftOptions) -> RgbaBuf {
-    println!("* Shifting blocks");
-
-    ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
-        for block in &config.blocks {
-            if y < block.min_y { break; }
-            if y >= block.min_y && y < block.min_y + block.height {
-                return img.get_pixel(clamping_add(x, block.shift, img.width() - 1), y);
-            }
-        }
+fn file_stem(path: &Path) -> String {
+    path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned())
+}
 
-        img.get_pixel(x, y)
-    })
-}
\ No newline at end of file
+fn glitch_img(img: &RgbaBuf, pipeline: &[Box<dyn Effect>], rng: &mut GlitchRng) -> RgbaBuf {
+    pipeline.iter().fold(img.clone(), |img, effect| effect.apply(&img, rng))
+}
@@ -0,0 +1,128 @@
+use image::{self, GrayImage, Pixel};
+use noise::{self, NoiseModule, Seedable};
+use std::rc::Rc;
+use effects::parse_param;
+
+/// The shape of a mask, which is 1 where an effect applies and 0 where it does not
+#[derive(Clone)]
+pub enum MaskShape {
+    /// Brightness of a grayscale image, stretched to the size of the frame
+    Image(Rc<GrayImage>),
+    /// Rectangles given as x, y, width and height in pixels
+    Rects(Vec<(u32, u32, u32, u32)>),
+    /// Linear ramp towards an angle in degrees counterclockwise from the right, between two
+    /// positions from 0 to 1 along the way
+    Gradient { angle: f64, start: f64, end: f64 },
+    /// Perlin noise with `frequency` features across the width, set where it exceeds `threshold`
+    Noise { frequency: f64, threshold: f64, seed: u32 },
+}
+
+impl MaskShape {
+    /// Parse a mask spec such as `image:mask.png`, `rect:0:0:200:100;300:50:20:20`, `gradient:90`,
+    /// `gradient:0:0.3:0.7` or `noise:4:0.6`
+    pub fn parse(spec: &str, seed: u32) -> Result<MaskShape, String> {
+        let (kind, args) = match spec.find(':') {
+            Some(colon) => (&spec[..colon], &spec[colon + 1..]),
+            None => (spec, ""),
+        };
+        let numbers = |args: &str, min: usize, max: usize| {
+            let values = args.split(':')
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| parse_param::<f64>(kind, arg))
+                .collect::<Result<Vec<_>, _>>()?;
+            if values.len() < min || values.len() > max {
+                let expected = if min == max { min.to_string() } else { format!("{} to {}", min, max) };
+                return Err(format!("Expected {} values for mask '{}', found {}", expected, kind, values.len()));
+            }
+            Ok(values)
+        };
+
+        match kind {
+            "image" => image::open(args)
+                .map(|img| MaskShape::Image(Rc::new(img.to_luma8())))
+                .map_err(|e| format!("Unable to load mask '{}': {}", args, e)),
+            "rect" => {
+                let rects = args.split(';')
+                    .map(|rect| numbers(rect, 4, 4).map(|v| (v[0] as u32, v[1] as u32, v[2] as u32, v[3] as u32)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(MaskShape::Rects(rects))
+            },
+            "gradient" => {
+                let values = numbers(args, 1, 3)?;
+                Ok(MaskShape::Gradient {
+                    angle: values[0],
+                    start: values.get(1).cloned().unwrap_or(0.0),
+                    end: values.get(2).cloned().unwrap_or(1.0),
+                })
+            },
+            "noise" => {
+                let values = numbers(args, 1, 2)?;
+                Ok(MaskShape::Noise {
+                    frequency: values[0],
+                    threshold: values.get(1).cloned().unwrap_or(0.5),
+                    seed,
+                })
+            },
+            _ => Err(format!("Unknown mask '{}', expected image, rect, gradient or noise", kind)),
+        }
+    }
+
+    /// Mask values from 0 to 1 for every pixel of a frame of the given size
+    pub fn render(&self, width: u32, height: u32) -> Vec<f64> {
+        let mut values = vec![0.0; (width * height) as usize];
+        match *self {
+            MaskShape::Rects(ref rects) => {
+                for &(x, y, w, h) in rects {
+                    for py in u32::min(y, height)..u32::min(y.saturating_add(h), height) {
+                        for px in u32::min(x, width)..u32::min(x.saturating_add(w), width) {
+                            values[(py * width + px) as usize] = 1.0;
+                        }
+                    }
+                }
+            },
+            _ => {
+                let noise = match *self {
+                    MaskShape::Noise { seed, .. } => Some(noise::Perlin::new().set_seed(seed as usize)),
+                    _ => None,
+                };
+                for y in 0..height {
+                    for x in 0..width {
+                        values[(y * width + x) as usize] = self.value(x, y, width, height, &noise);
+                    }
+                }
+            },
+        }
+        values
+    }
+
+    fn value(&self, x: u32, y: u32, width: u32, height: u32, noise: &Option<noise::Perlin>) -> f64 {
+        match *self {
+            MaskShape::Image(ref img) => {
+                let value = img.get_pixel(x * img.width() / width, y * img.height() / height).channels()[0];
+                value as f64 / u8::MAX as f64
+            },
+            MaskShape::Rects(_) => unreachable!("Rectangles are drawn directly"),
+            MaskShape::Gradient { angle, start, end } => {
+                let radians = angle.to_radians();
+                let (dx, dy) = (radians.cos(), -radians.sin());
+                // Position along the direction, from 0 at the first corner reached to 1 at the last one
+                let u = (x as f64 + 0.5) / width as f64 - 0.5;
+                let v = (y as f64 + 0.5) / height as f64 - 0.5;
+                let extent = (dx.abs() + dy.abs()) / 2.0;
+                let t = (u * dx + v * dy + extent) / (2.0 * extent);
+                if end > start {
+                    ((t - start) / (end - start)).clamp(0.0, 1.0)
+                } else if t >= start {
+                    1.0
+                } else {
+                    0.0
+                }
+            },
+            MaskShape::Noise { frequency, threshold, .. } => {
+                let scale = frequency / width as f64;
+                let value = noise.as_ref().map_or(0.0, |noise| noise.get([x as f64 * scale, y as f64 * scale]));
+                if (value + 1.0) / 2.0 > threshold { 1.0 } else { 0.0 }
+            },
+        }
+    }
+}
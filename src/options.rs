@@ -0,0 +1,100 @@
+use effects::{self, BitPlaneOptions, BlockShiftOptions, CompositeOptions, CrtOptions, CrushOptions, DatamoshOptions, DisplaceOptions, JpegOptions, LimitedShiftOptions, NoiseOptions, ScanlineOptions, ShiftOptions, SortOptions, TileOptions, VhsOptions, WindOptions};
+use keyframes::Track;
+use pipeline::{self, Stage};
+
+/// Settings for all effects of a render
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Options {
+    pub pipeline: Vec<Stage>,
+    pub keyframes: Vec<Track>,
+    #[serde(rename = "color-shift")]
+    pub shift: LimitedShiftOptions,
+    #[serde(rename = "rgb-shift")]
+    pub rgb_shift: ShiftOptions,
+    #[serde(rename = "scanlines")]
+    pub scan: ScanlineOptions,
+    pub wind: WindOptions,
+    pub blocks: BlockShiftOptions,
+    pub grain: NoiseOptions,
+    pub sort: SortOptions,
+    pub jpeg: JpegOptions,
+    pub datamosh: DatamoshOptions,
+    pub crush: CrushOptions,
+    pub bitplanes: BitPlaneOptions,
+    pub vhs: VhsOptions,
+    pub composite: CompositeOptions,
+    pub crt: CrtOptions,
+    pub tiles: TileOptions,
+    pub displace: DisplaceOptions,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            pipeline: pipeline::parse(effects::DEFAULT_PIPELINE).expect("Invalid default pipeline"),
+            keyframes: vec![],
+            shift: LimitedShiftOptions::default(),
+            rgb_shift: ShiftOptions::default(),
+            scan: ScanlineOptions::default(),
+            wind: WindOptions::default(),
+            blocks: BlockShiftOptions::default(),
+            grain: NoiseOptions::default(),
+            sort: SortOptions::default(),
+            jpeg: JpegOptions::default(),
+            datamosh: DatamoshOptions::default(),
+            crush: CrushOptions::default(),
+            bitplanes: BitPlaneOptions::default(),
+            vhs: VhsOptions::default(),
+            composite: CompositeOptions::default(),
+            crt: CrtOptions::default(),
+            tiles: TileOptions::default(),
+            displace: DisplaceOptions::default(),
+        }
+    }
+}
+
+impl Options {
+    /// Replace the red/cyan color shift with the per-channel rgb shift, in the pipeline as well as
+    /// in keyframes. The shift distance becomes the offset of the channels.
+    pub fn use_rgb_shift(&mut self) {
+        for stage in self.pipeline.iter_mut().filter(|stage| stage.effect == "color-shift") {
+            stage.effect = String::from("rgb-shift");
+            for &mut (ref mut key, _) in stage.params.iter_mut() {
+                if key == "distance" {
+                    *key = String::from("offset");
+                }
+            }
+        }
+        for track in self.keyframes.iter_mut().filter(|track| track.effect == "color-shift") {
+            track.effect = String::from("rgb-shift");
+            if track.param == "distance" {
+                track.param = String::from("offset");
+            }
+        }
+    }
+
+    /// Replace the rgb shift with the red/cyan color shift, the reverse of `use_rgb_shift`. The offset
+    /// of the channels becomes the shift distance. Fails for parameters only the rgb shift has.
+    pub fn use_red_cyan_shift(&mut self) -> Result<(), String> {
+        let convert = |param: &mut String| match param.as_str() {
+            "offset" => {
+                *param = String::from("distance");
+                Ok(())
+            },
+            _ => Err(format!("Parameter '{}' of effect 'rgb-shift' has no red/cyan equivalent", param)),
+        };
+        for stage in self.pipeline.iter_mut().filter(|stage| stage.effect == "rgb-shift") {
+            stage.effect = String::from("color-shift");
+            for &mut (ref mut key, _) in stage.params.iter_mut() {
+                convert(key)?;
+            }
+        }
+        for track in self.keyframes.iter_mut().filter(|track| track.effect == "rgb-shift") {
+            track.effect = String::from("color-shift");
+            convert(&mut track.param)?;
+        }
+        self.shift.distance = self.rgb_shift.offset.round() as i32;
+        Ok(())
+    }
+}
@@ -0,0 +1,622 @@
+use gif;
+use image_webp;
+use png;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use util::RgbaBuf;
+
+/// Container formats frames can be written to
+#[derive(Copy, Clone)]
+pub enum Format {
+    /// One image file per frame, encoded according to the file extension
+    Sequence,
+    /// A single animated GIF
+    Gif,
+    /// A single animated PNG
+    Apng,
+    /// A single lossless animated WebP
+    WebP,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "png" => Some(Format::Sequence),
+            "gif" => Some(Format::Gif),
+            "apng" => Some(Format::Apng),
+            "webp" => Some(Format::WebP),
+            _ => None,
+        }
+    }
+
+    /// The format written to a path. Extensions of animated formats select those, anything else
+    /// becomes an image sequence.
+    pub fn from_path(path: &str) -> Format {
+        let extension = Path::new(path).extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("gif") => Format::Gif,
+            Some("apng") => Format::Apng,
+            Some("webp") => Format::WebP,
+            _ => Format::Sequence,
+        }
+    }
+
+    /// Default file extension
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            Format::Sequence | Format::Apng => "png",
+            Format::Gif => "gif",
+            Format::WebP => "webp",
+        }
+    }
+
+    /// Output template used when none is given
+    pub fn default_template(&self) -> &'static str {
+        match *self {
+            Format::Sequence => "glitch_{frame}.png",
+            Format::Gif => "glitch.gif",
+            Format::Apng => "glitch.png",
+            Format::WebP => "glitch.webp",
+        }
+    }
+}
+
+/// Values substituted into output path templates
+#[derive(Clone)]
+pub struct TemplateVars {
+    pub stem: String,
+    pub seed: u64,
+    pub preset: String,
+    pub ext: String,
+}
+
+#[derive(Clone)]
+enum Placeholder {
+    Text(String),
+    Stem,
+    /// The frame index, zero-padded to the given width
+    Frame(usize),
+    Seed,
+    Preset,
+    Ext,
+}
+
+/// An output path such as `out/{stem}_{frame:04}.{ext}`
+#[derive(Clone)]
+pub struct PathTemplate {
+    parts: Vec<Placeholder>,
+}
+
+impl PathTemplate {
+    /// Parse a template. `{{` and `}}` stand for literal braces.
+    pub fn parse(template: &str) -> Result<PathTemplate, String> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                },
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("Unbalanced '{{' in output template '{}'", template)),
+                        }
+                    }
+                    if !text.is_empty() {
+                        parts.push(Placeholder::Text(text.clone()));
+                        text.clear();
+                    }
+                    parts.push(PathTemplate::placeholder(&name)?);
+                },
+                '}' => return Err(format!("Unbalanced '}}' in output template '{}'", template)),
+                _ => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Placeholder::Text(text));
+        }
+
+        Ok(PathTemplate { parts })
+    }
+
+    fn placeholder(name: &str) -> Result<Placeholder, String> {
+        let mut name_parts = name.splitn(2, ':');
+        let placeholder = match (name_parts.next().unwrap_or("").trim(), name_parts.next()) {
+            ("stem", None) => Placeholder::Stem,
+            ("frame", None) => Placeholder::Frame(0),
+            ("frame", Some(width)) => Placeholder::Frame(width.trim().parse()
+                .map_err(|_| format!("Invalid frame number width '{}', expected e.g. {{frame:04}}", width))?),
+            ("seed", None) => Placeholder::Seed,
+            ("preset", None) => Placeholder::Preset,
+            ("ext", None) => Placeholder::Ext,
+            _ => return Err(format!("Unknown placeholder '{{{}}}' in output template, expected stem, frame, seed, preset or ext", name)),
+        };
+        Ok(placeholder)
+    }
+
+    /// Whether each frame is written to its own path
+    pub fn has_frame(&self) -> bool {
+        self.parts.iter().any(|part| matches!(*part, Placeholder::Frame(_)))
+    }
+
+    pub fn expand(&self, vars: &TemplateVars, frame: usize) -> String {
+        self.parts.iter().map(|part| match *part {
+            Placeholder::Text(ref text) => text.clone(),
+            Placeholder::Stem => vars.stem.clone(),
+            Placeholder::Frame(width) => format!("{:0width$}", frame, width = width),
+            Placeholder::Seed => vars.seed.to_string(),
+            Placeholder::Preset => vars.preset.clone(),
+            Placeholder::Ext => vars.ext.clone(),
+        }).collect()
+    }
+}
+
+/// Create a file, along with any missing parent directories
+fn create_file(path: &str) -> Result<File, String> {
+    create_parent_dirs(path)?;
+    File::create(path).map_err(|e| format!("Unable to write output image '{}': {}", path, e))
+}
+
+fn create_parent_dirs(path: &str) -> Result<(), String> {
+    match Path::new(path).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent)
+            .map_err(|e| format!("Unable to create output directory '{}': {}", parent.display(), e)),
+        _ => Ok(()),
+    }
+}
+
+/// Timing options of animated output formats
+#[derive(Copy, Clone)]
+pub struct AnimationOptions {
+    /// Display time of each frame in milliseconds
+    pub frame_delay: u32,
+    /// How often the animation is repeated after the first play, `None` for forever
+    pub repeat: Option<u16>,
+    /// Color quantization speed from 1 (best quality) to 30 (fastest)
+    pub gif_speed: i32,
+}
+
+/// Destination for rendered frames
+pub trait FrameWriter {
+    fn write_frame(&mut self, frame: &RgbaBuf) -> Result<(), String>;
+
+    /// Flush any pending output once all frames have been written
+    fn finish(&mut self) -> Result<(), String>;
+}
+
+/// Create a writer for the given format. Animations are written to the path of the first frame.
+pub fn writer(format: Format, template: PathTemplate, vars: TemplateVars, animation: &AnimationOptions) -> Box<dyn FrameWriter> {
+    let path = template.expand(&vars, 0);
+    match format {
+        Format::Sequence => Box::new(ImageSequence { template, vars, index: 0 }),
+        Format::Gif => Box::new(GifWriter { path, options: *animation, encoder: None }),
+        Format::Apng => Box::new(ApngWriter { path, options: *animation, size: None, frames: vec![] }),
+        Format::WebP => Box::new(WebPWriter { path, options: *animation, size: None, frames: vec![] }),
+    }
+}
+
+struct ImageSequence {
+    template: PathTemplate,
+    vars: TemplateVars,
+    index: usize,
+}
+
+impl FrameWriter for ImageSequence {
+    fn write_frame(&mut self, frame: &RgbaBuf) -> Result<(), String> {
+        let path = self.template.expand(&self.vars, self.index);
+        self.index += 1;
+        create_parent_dirs(&path)?;
+        frame.save(&path).map_err(|e| format!("Unable to save output image '{}': {}", path, e))
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+struct GifWriter {
+    path: String,
+    options: AnimationOptions,
+    encoder: Option<gif::Encoder<BufWriter<File>>>,
+}
+
+impl GifWriter {
+    fn error<E: ToString>(&self, e: E) -> String {
+        format!("Unable to write output image '{}': {}", self.path, e.to_string())
+    }
+}
+
+impl FrameWriter for GifWriter {
+    fn write_frame(&mut self, frame: &RgbaBuf) -> Result<(), String> {
+        if frame.width() > u16::MAX as u32 || frame.height() > u16::MAX as u32 {
+            return Err(self.error("image too large for GIF"));
+        }
+        let (width, height) = (frame.width() as u16, frame.height() as u16);
+
+        if self.encoder.is_none() {
+            let file = create_file(&self.path)?;
+            let mut encoder = gif::Encoder::new(BufWriter::new(file), width, height, &[]).map_err(|e| self.error(e))?;
+            let repeat = match self.options.repeat {
+                Some(count) => gif::Repeat::Finite(count),
+                None => gif::Repeat::Infinite,
+            };
+            encoder.set_repeat(repeat).map_err(|e| self.error(e))?;
+            self.encoder = Some(encoder);
+        }
+
+        let mut pixels = frame.clone().into_raw();
+        let mut gif_frame = gif::Frame::from_rgba_speed(width, height, &mut pixels, self.options.gif_speed);
+        // GIF delays are given in units of 10ms
+        gif_frame.delay = u32::min(self.options.frame_delay / 10, u16::MAX as u32) as u16;
+
+        let result = self.encoder.as_mut().map(|encoder| encoder.write_frame(&gif_frame));
+        match result {
+            Some(Err(e)) => Err(self.error(e)),
+            _ => Ok(()),
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        match self.encoder.take() {
+            Some(encoder) => {
+                let mut file = encoder.into_inner().map_err(|e| self.error(e))?;
+                file.flush().map_err(|e| self.error(e))
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+/// Collects compressed frames and writes the animated PNG once all frames are known
+struct ApngWriter {
+    path: String,
+    options: AnimationOptions,
+    size: Option<(u32, u32)>,
+    /// Compressed image data of each frame
+    frames: Vec<Vec<u8>>,
+}
+
+impl ApngWriter {
+    fn error<E: ToString>(&self, e: E) -> String {
+        format!("Unable to write output image '{}': {}", self.path, e.to_string())
+    }
+
+    /// The animated PNG of all frames written so far
+    fn encode(&self, width: u32, height: u32) -> Result<Vec<u8>, String> {
+        let mut apng = vec![];
+        {
+            let mut encoder = png::Encoder::new(&mut apng, width, height);
+            encoder.set_color(png::ColorType::RGBA);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().map_err(|e| self.error(e))?;
+
+            let mut actl = vec![];
+            actl.extend_from_slice(&(self.frames.len() as u32).to_be_bytes());
+            // APNG counts plays rather than repeats, with 0 meaning forever
+            let plays = self.options.repeat.map_or(0, |count| count as u32 + 1);
+            actl.extend_from_slice(&plays.to_be_bytes());
+            writer.write_chunk(png::chunk::acTL, &actl).map_err(|e| self.error(e))?;
+
+            // Frame control and data chunks share one sequence, the first frame doubles as the still image
+            let mut sequence = 0;
+            for (i, data) in self.frames.iter().enumerate() {
+                writer.write_chunk(png::chunk::fcTL, &self.frame_control(sequence, width, height))
+                    .map_err(|e| self.error(e))?;
+                sequence += 1;
+                if i == 0 {
+                    writer.write_chunk(png::chunk::IDAT, data).map_err(|e| self.error(e))?;
+                } else {
+                    let mut fdat = sequence.to_be_bytes().to_vec();
+                    fdat.extend_from_slice(data);
+                    writer.write_chunk(png::chunk::fdAT, &fdat).map_err(|e| self.error(e))?;
+                    sequence += 1;
+                }
+            }
+            // Dropping the writer adds the closing IEND chunk
+        }
+        Ok(apng)
+    }
+
+    /// The `fcTL` chunk describing a full-size frame
+    fn frame_control(&self, sequence: u32, width: u32, height: u32) -> Vec<u8> {
+        let (numerator, denominator) = if self.options.frame_delay <= u16::MAX as u32 {
+            (self.options.frame_delay as u16, 1000u16)
+        } else {
+            (u32::min(self.options.frame_delay / 10, u16::MAX as u32) as u16, 100)
+        };
+
+        let mut fctl = vec![];
+        fctl.extend_from_slice(&sequence.to_be_bytes());
+        fctl.extend_from_slice(&width.to_be_bytes());
+        fctl.extend_from_slice(&height.to_be_bytes());
+        fctl.extend_from_slice(&0u32.to_be_bytes());
+        fctl.extend_from_slice(&0u32.to_be_bytes());
+        fctl.extend_from_slice(&numerator.to_be_bytes());
+        fctl.extend_from_slice(&denominator.to_be_bytes());
+        // Keep the frame on the canvas and replace the previous frame instead of blending onto it
+        fctl.extend_from_slice(&[0, 0]);
+        fctl
+    }
+}
+
+/// The concatenated `IDAT` data of a PNG file
+fn image_data(png: &[u8]) -> Option<Vec<u8>> {
+    const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    if png.len() < SIGNATURE.len() || png[..SIGNATURE.len()] != SIGNATURE {
+        return None;
+    }
+
+    let mut data = vec![];
+    let mut pos = SIGNATURE.len();
+    // Each chunk consists of its length, type, data and CRC
+    while pos + 8 <= png.len() {
+        let length = u32::from_be_bytes([png[pos], png[pos + 1], png[pos + 2], png[pos + 3]]) as usize;
+        let end = pos + 8 + length + 4;
+        if end > png.len() {
+            return None;
+        }
+        if png[pos + 4..pos + 8] == png::chunk::IDAT {
+            data.extend_from_slice(&png[pos + 8..pos + 8 + length]);
+        }
+        pos = end;
+    }
+    Some(data)
+}
+
+impl FrameWriter for ApngWriter {
+    fn write_frame(&mut self, frame: &RgbaBuf) -> Result<(), String> {
+        match self.size {
+            Some(size) if size != frame.dimensions() => return Err(self.error("frames differ in size")),
+            _ => self.size = Some(frame.dimensions()),
+        }
+
+        // The encoder produces a complete still image, of which only the image data is needed
+        let mut still = vec![];
+        {
+            let mut encoder = png::Encoder::new(&mut still, frame.width(), frame.height());
+            encoder.set_color(png::ColorType::RGBA);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().map_err(|e| self.error(e))?;
+            writer.write_image_data(frame).map_err(|e| self.error(e))?;
+        }
+        let data = image_data(&still).ok_or_else(|| self.error("unexpected PNG encoder output"))?;
+        self.frames.push(data);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        let (width, height) = match self.size {
+            Some(size) => size,
+            None => return Ok(()),
+        };
+
+        let apng = self.encode(width, height)?;
+        let mut file = BufWriter::new(create_file(&self.path)?);
+        file.write_all(&apng).map_err(|e| self.error(e))?;
+        file.flush().map_err(|e| self.error(e))
+    }
+}
+
+/// Collects lossless frames and writes the animated WebP container once all frames are known
+struct WebPWriter {
+    path: String,
+    options: AnimationOptions,
+    size: Option<(u32, u32)>,
+    /// Encoded `ANMF` chunks
+    frames: Vec<u8>,
+}
+
+impl WebPWriter {
+    fn error<E: ToString>(&self, e: E) -> String {
+        format!("Unable to write output image '{}': {}", self.path, e.to_string())
+    }
+}
+
+/// Append a RIFF chunk, padded to an even length
+fn write_chunk(out: &mut Vec<u8>, name: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(name);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// Append the lowest 24 bits of a value
+fn write_u24(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes()[..3]);
+}
+
+impl FrameWriter for WebPWriter {
+    fn write_frame(&mut self, frame: &RgbaBuf) -> Result<(), String> {
+        const MAX_DIMENSION: u32 = 1 << 14;
+        if frame.width() > MAX_DIMENSION || frame.height() > MAX_DIMENSION {
+            return Err(self.error("image too large for WebP"));
+        }
+        self.size = Some((frame.width(), frame.height()));
+
+        // The encoder produces a complete still image, of which only the VP8L chunk is needed
+        let mut still = vec![];
+        image_webp::WebPEncoder::new(&mut still)
+            .encode(frame, frame.width(), frame.height(), image_webp::ColorType::Rgba8)
+            .map_err(|e| self.error(e))?;
+        if still.get(12..16) != Some(&b"VP8L"[..]) {
+            return Err(self.error("unexpected WebP encoder output, expected a lossless VP8L chunk"));
+        }
+        let bitstream = &still[12..];
+
+        let mut anmf = vec![];
+        write_u24(&mut anmf, 0);
+        write_u24(&mut anmf, 0);
+        write_u24(&mut anmf, frame.width() - 1);
+        write_u24(&mut anmf, frame.height() - 1);
+        write_u24(&mut anmf, u32::min(self.options.frame_delay, 0xFF_FFFF));
+        // Replace the canvas instead of alpha-blending onto the previous frame
+        anmf.push(0b10);
+        anmf.extend_from_slice(bitstream);
+        write_chunk(&mut self.frames, b"ANMF", &anmf);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        let (width, height) = match self.size {
+            Some(size) => size,
+            None => return Ok(()),
+        };
+
+        let mut vp8x = vec![];
+        // Animation and alpha flags
+        vp8x.push(0b0001_0010);
+        write_u24(&mut vp8x, 0);
+        write_u24(&mut vp8x, width - 1);
+        write_u24(&mut vp8x, height - 1);
+
+        let mut anim = vec![];
+        // Transparent background
+        anim.extend_from_slice(&[0, 0, 0, 0]);
+        // WebP counts plays rather than repeats, with 0 meaning forever
+        let plays = self.options.repeat.map_or(0, |count| u16::saturating_add(count, 1));
+        anim.extend_from_slice(&plays.to_le_bytes());
+
+        let mut body = b"WEBP".to_vec();
+        write_chunk(&mut body, b"VP8X", &vp8x);
+        write_chunk(&mut body, b"ANIM", &anim);
+        body.extend_from_slice(&self.frames);
+
+        let mut file = BufWriter::new(create_file(&self.path)?);
+        file.write_all(b"RIFF").map_err(|e| self.error(e))?;
+        file.write_all(&(body.len() as u32).to_le_bytes()).map_err(|e| self.error(e))?;
+        file.write_all(&body).map_err(|e| self.error(e))?;
+        file.flush().map_err(|e| self.error(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgba};
+
+    /// Type and data of each chunk of a PNG file
+    fn chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        let mut chunks = vec![];
+        let mut pos = 8;
+        while pos < png.len() {
+            let length = u32::from_be_bytes([png[pos], png[pos + 1], png[pos + 2], png[pos + 3]]) as usize;
+            let kind = [png[pos + 4], png[pos + 5], png[pos + 6], png[pos + 7]];
+            chunks.push((kind, png[pos + 8..pos + 8 + length].to_vec()));
+            pos += 12 + length;
+        }
+        chunks
+    }
+
+    fn be_u32(bytes: &[u8]) -> u32 {
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn apng_writer(repeat: Option<u16>) -> ApngWriter {
+        let options = AnimationOptions { frame_delay: 80, repeat, gif_speed: 10 };
+        ApngWriter { path: String::from("test.png"), options, size: None, frames: vec![] }
+    }
+
+    fn vars() -> TemplateVars {
+        TemplateVars { stem: String::from("photo"), seed: 42, preset: String::from("vhs"), ext: String::from("png") }
+    }
+
+    fn expand(template: &str, frame: usize) -> Result<String, String> {
+        PathTemplate::parse(template).map(|template| template.expand(&vars(), frame))
+    }
+
+    #[test]
+    fn expands_placeholders() {
+        assert_eq!(expand("out/{stem}_{seed}_{preset}.{ext}", 0), Ok(String::from("out/photo_42_vhs.png")));
+        assert_eq!(expand("{stem}-{frame}.{ext}", 7), Ok(String::from("photo-7.png")));
+        assert_eq!(expand("{stem}-{frame:04}.{ext}", 7), Ok(String::from("photo-0007.png")));
+        assert_eq!(expand("{frame:2}", 123), Ok(String::from("123")));
+        assert_eq!(expand("glitch.gif", 3), Ok(String::from("glitch.gif")));
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(expand("{{stem}}_{stem}", 0), Ok(String::from("{stem}_photo")));
+        assert_eq!(expand("a}}b{{", 0), Ok(String::from("a}b{")));
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        let error = |template: &str| PathTemplate::parse(template).err().unwrap();
+        assert!(error("{name}.png").contains("Unknown placeholder '{name}'"));
+        assert!(error("{seed:4}.png").contains("Unknown placeholder"));
+        assert!(error("{frame:four}.png").contains("Invalid frame number width 'four'"));
+        assert!(error("out}.png").contains("Unbalanced '}'"));
+        assert!(error("out_{frame.png").contains("Unbalanced '{'"));
+        assert!(error("out_{").contains("Unbalanced '{'"));
+    }
+
+    #[test]
+    fn knows_whether_frames_get_own_paths() {
+        assert!(PathTemplate::parse("{stem}_{frame:03}.png").unwrap().has_frame());
+        assert!(!PathTemplate::parse("{stem}_{{frame}}.png").unwrap().has_frame());
+    }
+
+    #[test]
+    fn image_data_joins_idat_chunks() {
+        let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        for &(kind, data) in [(b"IHDR", &b"head"[..]), (b"IDAT", &b"ab"[..]), (b"tEXt", &b"x"[..]), (b"IDAT", &b"cd"[..])].iter() {
+            png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            png.extend_from_slice(kind);
+            png.extend_from_slice(data);
+            png.extend_from_slice(&[0; 4]);
+        }
+        assert_eq!(image_data(&png), Some(b"abcd".to_vec()));
+
+        assert_eq!(image_data(b"GIF89a"), None);
+        png.truncate(png.len() - 3);
+        assert_eq!(image_data(&png), None);
+    }
+
+    #[test]
+    fn apng_numbers_frame_chunks_in_sequence() {
+        let mut writer = apng_writer(Some(2));
+        for shade in 0..3 {
+            writer.write_frame(&ImageBuffer::from_pixel(4, 3, Rgba([shade * 100, 0, 0, 255]))).unwrap();
+        }
+        let chunks = chunks(&writer.encode(4, 3).unwrap());
+
+        let kinds = chunks.iter().map(|&(kind, _)| kind).collect::<Vec<_>>();
+        assert_eq!(kinds, [*b"IHDR", *b"acTL", *b"fcTL", *b"IDAT", *b"fcTL", *b"fdAT", *b"fcTL", *b"fdAT", *b"IEND"]);
+
+        // Three frames, played three times
+        assert_eq!(be_u32(&chunks[1].1[0..]), 3);
+        assert_eq!(be_u32(&chunks[1].1[4..]), 3);
+
+        let sequence = chunks.iter()
+            .filter(|&&(kind, _)| kind == *b"fcTL" || kind == *b"fdAT")
+            .map(|(_, data)| be_u32(data))
+            .collect::<Vec<_>>();
+        assert_eq!(sequence, [0, 1, 2, 3, 4]);
+
+        let fctl = &chunks[2].1;
+        assert_eq!((be_u32(&fctl[4..]), be_u32(&fctl[8..])), (4, 3));
+        assert_eq!(&fctl[20..24], &[0, 80, 0x03, 0xE8]);
+    }
+
+    #[test]
+    fn apng_rejects_frames_of_different_sizes() {
+        let mut writer = apng_writer(None);
+        writer.write_frame(&ImageBuffer::new(4, 3)).unwrap();
+        assert!(writer.write_frame(&ImageBuffer::new(3, 4)).is_err());
+    }
+}
@@ -0,0 +1,217 @@
+use std::convert::TryFrom;
+use std::fmt;
+use effects::{self, Effect, RenderInfo};
+use layer::Layer;
+use options::Options;
+use util::GlitchRng;
+
+/// A single entry of a pipeline spec: an effect and parameters overriding the render options
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Stage {
+    pub effect: String,
+    pub params: Vec<(String, String)>,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.effect)?;
+        if !self.params.is_empty() {
+            let params = self.params.iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<_>>();
+            write!(f, "({})", params.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Stage> for String {
+    fn from(stage: Stage) -> String {
+        stage.to_string()
+    }
+}
+
+impl TryFrom<String> for Stage {
+    type Error = String;
+
+    fn try_from(spec: String) -> Result<Stage, String> {
+        let mut stages = parse(&spec)?;
+        if stages.len() != 1 {
+            return Err(format!("Expected a single stage, found '{}'", spec));
+        }
+        Ok(stages.remove(0))
+    }
+}
+
+/// Parse a pipeline spec such as `scanlines,wind(onset=0.01),blocks,wind(onset=0.2,continue=0.5)`.
+///
+/// Stages are separated by commas or newlines. Everything after a `#` up to the end of the line
+/// is ignored, so specs can be read from a file.
+pub fn parse(spec: &str) -> Result<Vec<Stage>, String> {
+    let spec = spec.lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut stages = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in spec.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Err(String::from("Unbalanced ')' in pipeline")),
+            ')' => depth -= 1,
+            ',' | '\n' if depth == 0 => {
+                stages.extend(parse_stage(&spec[start..i])?);
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+    if depth != 0 {
+        return Err(String::from("Unbalanced '(' in pipeline"));
+    }
+    stages.extend(parse_stage(&spec[start..])?);
+
+    Ok(stages)
+}
+
+fn parse_stage(stage: &str) -> Result<Option<Stage>, String> {
+    let stage = stage.trim();
+    if stage.is_empty() {
+        return Ok(None);
+    }
+
+    let (name, params) = match stage.find('(') {
+        Some(open) => {
+            if !stage.ends_with(')') {
+                return Err(format!("Unexpected input after parameters of stage '{}'", stage));
+            }
+            (stage[..open].trim(), &stage[open + 1..stage.len() - 1])
+        },
+        None => (stage, ""),
+    };
+
+    if effects::lookup(name).is_none() {
+        return Err(format!("Unknown effect '{}'", name));
+    }
+
+    let mut parsed_params = vec![];
+    for param in params.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let mut parts = param.splitn(2, '=');
+        let key = parts.next().unwrap_or("").trim();
+        let value = parts.next()
+            .ok_or_else(|| format!("Expected 'key=value' for parameter '{}' of '{}'", param, name))?
+            .trim();
+        parsed_params.push((String::from(key), String::from(value)));
+    }
+
+    Ok(Some(Stage {
+        effect: String::from(name),
+        params: parsed_params,
+    }))
+}
+
+/// Construct the effects of a pipeline
+pub fn build(stages: &[Stage], opts: &Options, info: &RenderInfo, rng: &mut GlitchRng) -> Result<Vec<Box<dyn Effect>>, String> {
+    let mut pipeline = vec![];
+    for stage in stages {
+        let effect = effects::lookup(&stage.effect)
+            .ok_or_else(|| format!("Unknown effect '{}'", stage.effect))?;
+
+        let mut stage_opts = opts.clone();
+        let mut layer_params = vec![];
+        for (key, value) in &stage.params {
+            if Layer::is_layer_param(key) {
+                layer_params.push((key.clone(), value.clone()));
+                continue;
+            }
+            (effect.params)(&mut stage_opts).set_param(key, value)
+                .map_err(|e| format!("{} in stage '{}'", e, stage.effect))?;
+        }
+
+        let built = (effect.build)(&stage_opts, info, rng)
+            .map_err(|e| format!("{} in stage '{}'", e, stage.effect))?;
+        if layer_params.is_empty() {
+            pipeline.push(built);
+        } else {
+            let layer = Layer::new(built, &layer_params, rng)
+                .map_err(|e| format!("{} in stage '{}'", e, stage.effect))?;
+            pipeline.push(Box::new(layer));
+        }
+    }
+
+    Ok(pipeline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::seeded_rng;
+
+    /// The number of effects built from a pipeline spec
+    fn build_spec(spec: &str) -> Result<usize, String> {
+        let info = RenderInfo { width: 32, height: 24, period: None };
+        build(&parse(spec)?, &Options::default(), &info, &mut seeded_rng(0)).map(|pipeline| pipeline.len())
+    }
+
+    fn names(stages: &[Stage]) -> Vec<&str> {
+        stages.iter().map(|stage| stage.effect.as_str()).collect()
+    }
+
+    #[test]
+    fn parses_stages_in_order() {
+        let stages = parse("scanlines, wind ,blocks,wind").unwrap();
+        assert_eq!(names(&stages), ["scanlines", "wind", "blocks", "wind"]);
+        assert!(stages.iter().all(|stage| stage.params.is_empty()));
+    }
+
+    #[test]
+    fn parses_params_containing_commas() {
+        let stages = parse("wind(onset=0.01, continue=0.5),blocks(count=3)").unwrap();
+        assert_eq!(names(&stages), ["wind", "blocks"]);
+        assert_eq!(stages[0].params, [
+            (String::from("onset"), String::from("0.01")),
+            (String::from("continue"), String::from("0.5")),
+        ]);
+        assert_eq!(stages[1].params, [(String::from("count"), String::from("3"))]);
+    }
+
+    #[test]
+    fn ignores_comments_and_blank_lines() {
+        let spec = "# A pipeline file\nscanlines # dense\n\nwind(onset=0.1) # wind, streaks\n";
+        let stages = parse(spec).unwrap();
+        assert_eq!(names(&stages), ["scanlines", "wind"]);
+        assert_eq!(stages[1].params.len(), 1);
+    }
+
+    #[test]
+    fn rejects_malformed_specs() {
+        assert!(parse("wind(onset=0.1").err().unwrap().contains("Unbalanced '('"));
+        assert!(parse("wind)").err().unwrap().contains("Unbalanced ')'"));
+        assert!(parse("wind(onset=0.1)x").err().unwrap().contains("Unexpected input"));
+        assert!(parse("wind(onset)").err().unwrap().contains("Expected 'key=value'"));
+        assert!(parse("tornado").err().unwrap().contains("Unknown effect 'tornado'"));
+    }
+
+    #[test]
+    fn stages_round_trip_through_strings() {
+        let stage = Stage::try_from(String::from("wind(onset=0.1,continue=0.5)")).unwrap();
+        assert_eq!(String::from(stage), "wind(onset=0.1,continue=0.5)");
+        assert!(Stage::try_from(String::from("wind,blocks")).is_err());
+    }
+
+    #[test]
+    fn effect_params_reach_the_effect() {
+        assert!(build_spec("crt(mask=shadow,mask-strength=0.5)").is_ok());
+        assert!(build_spec("crt(mask=gradient:90)").unwrap_err().contains("for parameter 'mask' in stage 'crt'"));
+    }
+
+    #[test]
+    fn layer_params_reach_the_layer() {
+        assert!(build_spec("crt(mask=slot,region=gradient:90,region-feather=2,region-invert=true,opacity=0.5,blend-mode=screen)").is_ok());
+        assert!(build_spec("wind(region=shadow)").unwrap_err().contains("Unknown mask"));
+        assert!(build_spec("wind(region-size=2)").unwrap_err().contains("Unknown parameter 'region-size'"));
+    }
+}
@@ -0,0 +1,104 @@
+use effects::{BlockShiftOptions, LimitedShiftOptions, NoiseOptions, ScanlineOptions, ShiftOptions, WindOptions};
+use options::Options;
+use pipeline::{self, Stage};
+
+/// A named set of render options
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub options: fn() -> Options,
+}
+
+/// All built-in presets, selectable by name
+pub static PRESETS: &[Preset] = &[
+    Preset {
+        name: "subtle",
+        description: "Faint scanlines, a slight color fringe and some grain",
+        options: subtle,
+    },
+    Preset {
+        name: "vhs",
+        description: "Dense, wobbly scanlines with color bleed and streaks",
+        options: vhs,
+    },
+    Preset {
+        name: "cyberkitten",
+        description: "Wide scanlines without desync and short wind streaks",
+        options: cyberkitten,
+    },
+    Preset {
+        name: "cyberpunk",
+        description: "Wide scanlines, independently drifting color channels and heavy block shifts",
+        options: cyberpunk,
+    },
+    Preset {
+        name: "datamosh-lite",
+        description: "Many shifted blocks smeared by long wind streaks",
+        options: datamosh_lite,
+    },
+];
+
+/// Look up a preset by name
+pub fn lookup(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|preset| preset.name == name)
+}
+
+fn stages(spec: &str) -> Vec<Stage> {
+    pipeline::parse(spec).expect("Invalid pipeline in preset")
+}
+
+fn subtle() -> Options {
+    Options {
+        pipeline: stages("scanlines,color-shift,grain"),
+        shift: LimitedShiftOptions::new(1),
+        scan: ScanlineOptions::new(4, 1, 1.0, 0.3),
+        grain: NoiseOptions {
+            alpha: 0.1,
+            ..NoiseOptions::default()
+        },
+        ..Options::default()
+    }
+}
+
+fn vhs() -> Options {
+    Options {
+        pipeline: stages("scanlines,color-shift,wind,grain"),
+        shift: LimitedShiftOptions::new(3),
+        scan: ScanlineOptions::new(2, 1, 3.0, 0.8),
+        wind: WindOptions::new(0.01, 0.3),
+        grain: NoiseOptions {
+            alpha: 0.15,
+            ..NoiseOptions::default()
+        },
+        ..Options::default()
+    }
+}
+
+fn cyberkitten() -> Options {
+    Options {
+        scan: ScanlineOptions::new(12, 6, 0.0, 0.3),
+        wind: WindOptions::new(0.01, 0.2),
+        ..Options::default()
+    }
+}
+
+fn cyberpunk() -> Options {
+    Options {
+        pipeline: stages("scanlines,rgb-shift,wind,blocks"),
+        rgb_shift: ShiftOptions::new(6.0, 3.0, 2.0),
+        scan: ScanlineOptions::new(12, 6, 2.0, 0.3),
+        wind: WindOptions::new(0.01, 0.2),
+        blocks: BlockShiftOptions::new(8),
+        ..Options::default()
+    }
+}
+
+fn datamosh_lite() -> Options {
+    Options {
+        pipeline: stages("blocks,wind,color-shift,blocks(count=3)"),
+        shift: LimitedShiftOptions::new(2),
+        wind: WindOptions::new(0.02, 0.1),
+        blocks: BlockShiftOptions::new(12),
+        ..Options::default()
+    }
+}
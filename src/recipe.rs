@@ -0,0 +1,42 @@
+use serde_json;
+use std::fs;
+use std::path::Path;
+use toml;
+use options::Options;
+
+/// File formats a recipe can be stored in
+#[derive(Copy, Clone)]
+pub enum Format {
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// Guess the format from a file extension, defaulting to TOML
+    pub fn from_path(path: &str) -> Format {
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Format::Json,
+            _ => Format::Toml,
+        }
+    }
+}
+
+/// Load render options from a recipe file. Missing entries take their default values.
+pub fn load(path: &str) -> Result<Options, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read recipe '{}': {}", path, e))?;
+
+    let opts = match Format::from_path(path) {
+        Format::Toml => toml::from_str(&content).map_err(|e| e.to_string()),
+        Format::Json => serde_json::from_str(&content).map_err(|e| e.to_string()),
+    };
+    opts.map_err(|e| format!("Invalid recipe '{}': {}", path, e))
+}
+
+/// Serialize render options as a recipe
+pub fn dump(opts: &Options, format: Format) -> Result<String, String> {
+    match format {
+        Format::Toml => toml::to_string_pretty(opts).map_err(|e| e.to_string()),
+        Format::Json => serde_json::to_string_pretty(opts).map_err(|e| e.to_string()),
+    }
+}
@@ -0,0 +1,156 @@
+use image::{GenericImage, ImageBuffer, Rgba, Pixel};
+use rand::{SeedableRng, XorShiftRng};
+use std::borrow::Borrow;
+
+pub type RgbaBuf = ImageBuffer<Rgba<u8>, Vec<<Rgba<u8> as Pixel>::Subpixel>>;
+
+/// The random number generator shared by all effects of a render
+pub type GlitchRng = XorShiftRng;
+
+/// Create the render RNG. Identical seeds produce identical renders.
+pub fn seeded_rng(seed: u64) -> GlitchRng {
+    // XorShift must not be seeded with all zeroes, so the upper half is fixed
+    XorShiftRng::from_seed([seed as u32, (seed >> 32) as u32, 0x9E37_79B9, 0x7F4A_7C15])
+}
+
+// Poor man's typedef
+pub trait RgbaImage: GenericImage<Pixel=Rgba<u8>> {}
+
+impl<T> RgbaImage for T where T: GenericImage<Pixel=Rgba<u8>> {}
+
+pub fn clamping_add(val: u32, offset: i32, max: u32) -> u32 {
+    if -offset > val as i32 {
+        return 0;
+    }
+
+    let result = (offset + val as i32) as u32;
+    if result > max {
+        max
+    } else {
+        result
+    }
+}
+
+pub fn blend<T>(pixels: &[T]) -> Rgba<u8> where T: Borrow<Rgba<u8>> {
+    let alpha_values = pixels.iter().map(|px| px.borrow().channels()[3]);
+    let max_alpha = alpha_values.max().expect("Needs at least one pixel to blend");
+
+    if max_alpha == 0 {
+        return Rgba::from_channels(0, 0, 0, 0);
+    }
+
+    let max_alpha = (max_alpha as f64) / (u8::MAX as f64);
+
+    // To approximate light mixing, we set
+    // final_rgb[color] = max_{k < n} rgb_k[color] * alpha[k] / (max alpha)
+
+    let mut final_r = 0;
+    let mut final_g = 0;
+    let mut final_b = 0;
+    let mut final_a = 0.0;
+    for p in pixels {
+        let (r, g, b, a) = p.borrow().channels4();
+
+        let a = (a as f64) / (u8::MAX as f64);
+        let r = (r as f64) * a / max_alpha;
+        let g = (g as f64) * a / max_alpha;
+        let b = (b as f64) * a / max_alpha;
+        final_r = u8::max(final_r, r as u8);
+        final_g = u8::max(final_g, g as u8);
+        final_b = u8::max(final_b, b as u8);
+
+        final_a += (1.0 - final_a) * a;
+    }
+
+    Rgba::from_channels(final_r, final_g, final_b, (final_a * 255.0) as u8)
+}
+
+/// Mix `layer` into `base` by a weight from 0 to 1. Colors mix like light as in `blend`, while the
+/// alpha is interpolated, so opaque pixels stay opaque.
+pub fn blend_weighted(base: Rgba<u8>, layer: Rgba<u8>, weight: f64) -> Rgba<u8> {
+    if weight <= 0.0 {
+        return base;
+    } else if weight >= 1.0 {
+        return layer;
+    }
+
+    let fade = |px: Rgba<u8>, weight: f64| {
+        let (r, g, b, a) = px.channels4();
+        Rgba::from_channels(r, g, b, (a as f64 * weight).round() as u8)
+    };
+    let (r, g, b, _) = blend(&[fade(base, 1.0 - weight), fade(layer, weight)]).channels4();
+    let (base_alpha, layer_alpha) = (base.channels()[3] as f64, layer.channels()[3] as f64);
+    Rgba::from_channels(r, g, b, (base_alpha + (layer_alpha - base_alpha) * weight).round() as u8)
+}
+
+#[allow(dead_code)]
+pub fn blend_alpha_one_minus_alpha(a: f64, col1: u8, col2: u8) -> u8 {
+    let col1 = col1 as f64;
+    let col2 = col2 as f64;
+
+    f64::min(u8::MAX as f64, a * col1 + (1.0 - a) * col2) as u8
+}
+
+/// Convert RGB values between 0 and 1 to the YIQ color space of NTSC
+pub fn rgb_to_yiq(r: f64, g: f64, b: f64) -> (f64, f64, f64) {
+    (
+        0.299 * r + 0.587 * g + 0.114 * b,
+        0.5959 * r - 0.2746 * g - 0.3213 * b,
+        0.2115 * r - 0.5227 * g + 0.3112 * b,
+    )
+}
+
+/// Convert YIQ values back to RGB values, clamped to the range from 0 to 1
+pub fn yiq_to_rgb(y: f64, i: f64, q: f64) -> (f64, f64, f64) {
+    let clamp = |v: f64| v.clamp(0.0, 1.0);
+    (
+        clamp(y + 0.956 * i + 0.619 * q),
+        clamp(y - 0.272 * i - 0.647 * q),
+        clamp(y - 1.106 * i + 1.703 * q),
+    )
+}
+
+/// Convert RGB values between 0 and 1 to the YUV color space of PAL
+pub fn rgb_to_yuv(r: f64, g: f64, b: f64) -> (f64, f64, f64) {
+    (
+        0.299 * r + 0.587 * g + 0.114 * b,
+        -0.14713 * r - 0.28886 * g + 0.436 * b,
+        0.615 * r - 0.51499 * g - 0.10001 * b,
+    )
+}
+
+/// Convert YUV values back to RGB values, clamped to the range from 0 to 1
+pub fn yuv_to_rgb(y: f64, u: f64, v: f64) -> (f64, f64, f64) {
+    let clamp = |v: f64| v.clamp(0.0, 1.0);
+    (
+        clamp(y + 1.13983 * v),
+        clamp(y - 0.39465 * u - 0.58060 * v),
+        clamp(y + 2.03211 * u),
+    )
+}
+
+/// Blur values laid out row by row horizontally, then vertically with a box of the given radius
+pub fn box_blur(values: &[f64], width: u32, height: u32, radius: u32) -> Vec<f64> {
+    let (width, height, radius) = (width as usize, height as usize, radius as usize);
+    let average = |get: &dyn Fn(usize) -> f64, length: usize| {
+        (0..length)
+            .map(|i| {
+                let (start, end) = (i.saturating_sub(radius), usize::min(length, i + radius + 1));
+                (start..end).map(get).sum::<f64>() / (end - start) as f64
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut horizontal = Vec::with_capacity(values.len());
+    for y in 0..height {
+        horizontal.extend(average(&|x| values[y * width + x], width));
+    }
+
+    let mut blurred = vec![0.0; values.len()];
+    for x in 0..width {
+        for (y, value) in average(&|y| horizontal[y * width + x], height).into_iter().enumerate() {
+            blurred[y * width + x] = value;
+        }
+    }
+    blurred
+}